@@ -0,0 +1,89 @@
+// Measures how deep the engine searches with a fixed budget of searched positions
+// Used to compare changes to the search, since the depth reached within the same budget doesn't depend on the speed of the machine
+// Each position is searched with and without null move pruning and late move reductions, to show the depth they gain
+// cargo run --release --example benchmark --no-default-features --target x86_64-unknown-linux-gnu
+
+use chess2::algorithm::{self, AlphaBeta, CycleCounter, MoveHistory, SearchInfo};
+use chess2::board::board_representation::Board;
+use chess2::strength::EvalNoise;
+use chess2::time_management::TimeLimits;
+use chess2::transposition::TranspositionTable;
+
+// Number of positions searched (not counting quiescence search) before the search stops
+const NODE_BUDGET: u64 = 100000;
+
+const MAX_DEPTH: usize = 30;
+
+const POSITIONS: [&str; 6] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 b - - 0 1",
+];
+
+// Counts searched positions, the search calls update once for each position
+struct NodeCounter {
+    nodes: u64,
+}
+
+impl CycleCounter for NodeCounter {
+    fn update(&mut self) {
+        self.nodes += 1;
+    }
+
+    fn cycles(&self) -> u64 {
+        self.nodes
+    }
+}
+
+fn main() {
+    let pieces_info = chess2::piece::constants::gen();
+
+    let mut total_depths = [0; 2]; // Without and with reductions
+    for fen in POSITIONS {
+        println!("{}", fen);
+        for (i, reductions) in [false, true].into_iter().enumerate() {
+            let search_info = search(fen, reductions, &pieces_info);
+
+            let mut pv = String::new();
+            for piece_move in search_info.pv() {
+                pv += &format!(" {}", piece_move.to_uci().as_str());
+            }
+
+            println!("    reductions {:5} depth {} score {} nodes {} pv{}", reductions, search_info.depth, search_info.score, search_info.nodes, pv);
+            total_depths[i] += search_info.depth;
+        }
+    }
+
+    println!("Average depth without reductions {:.2}", total_depths[0] as f32 / POSITIONS.len() as f32);
+    println!("Average depth with reductions {:.2}", total_depths[1] as f32 / POSITIONS.len() as f32);
+}
+
+// Searches the position until the node budget is used
+fn search(fen: &str, reductions: bool, pieces_info: &[chess2::piece::constants::PieceInfo; 12]) -> SearchInfo {
+    let board = Board::from_fen(fen).unwrap();
+    let mut transposition_table = Box::new(TranspositionTable::<4096>::new());
+    let mut search_info = SearchInfo::new();
+    search_info.reductions = reductions;
+
+    algorithm::gen_best_move(
+        true,
+        &mut NodeCounter { nodes: 0 },
+        &0,
+        &TimeLimits::fixed(NODE_BUDGET),
+        MAX_DEPTH,
+        0,
+        AlphaBeta::new(),
+        &[[0; 64]; 12],
+        &EvalNoise::NONE,
+        &mut transposition_table,
+        &mut MoveHistory::new(),
+        &mut search_info,
+        board,
+        pieces_info,
+    );
+
+    search_info
+}
@@ -1,16 +1,32 @@
-use core::cmp::PartialOrd;
-use rtt_target::{rprintln, rtt_init_print};
+// Lints for the code style of the original search
+// E.g. late initialisation of variables with if/else blocks, and indexing arrays with loop variables
+#![allow(
+    clippy::collapsible_if,
+    clippy::needless_late_init,
+    clippy::needless_range_loop,
+    clippy::new_without_default,
+    clippy::redundant_field_names,
+)]
+
+// The search functions take the search state (tables, limits, statistics) as separate arguments, so each branch only copies the board
+#![allow(clippy::too_many_arguments)]
+
+use arrform::ArrForm;
 
 use crate::board::board_representation;
-use crate::board::move_generator::EnemyAttacks;
+use crate::board::move_generator::{EnemyAttacks, MoveList, MAX_SEARCH_MOVES};
 use crate::TeamBitboards;
+use crate::transposition::{Bound, TranspositionTable};
+use crate::time_management::TimeLimits;
+use crate::strength::{EvalNoise, Random};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Move {
     pub initial_piece_coordinates: board_representation::BoardCoordinates,
     pub final_piece_bit: usize,
-    pub value: i8,
-    pub heatmap_value: i16,
+    pub promotion: Option<usize>, // Board index of the piece a pawn promotes to
+    pub value: i8, // Material the move is expected to gain
+    pub order_value: i16, // Moves with higher order values are searched first
 }
 
 impl Move {
@@ -18,11 +34,37 @@ impl Move {
         Move {
             initial_piece_coordinates: board_representation::BoardCoordinates::new(),
             final_piece_bit: 0,
+            promotion: None,
             value: 0,
-            heatmap_value: 0,
+            order_value: 0,
         }
     }
 
+    // Returns true if both moves move the same piece to the same bit, and promote to the same piece
+    pub fn same_move(&self, other: &Move) -> bool {
+        self.initial_piece_coordinates == other.initial_piece_coordinates && self.final_piece_bit == other.final_piece_bit && self.promotion == other.promotion
+    }
+
+    // Formats the move in UCI long algebraic notation, e.g. "e7e8q"
+    pub fn to_uci(&self) -> ArrForm<{ crate::notation::UCI_MAX_LEN }> {
+        crate::notation::to_uci(self)
+    }
+
+    // Formats the move in standard algebraic notation for the board it is made on, e.g. "Nbd7+"
+    pub fn to_san(&self, board: &board_representation::Board, pieces_info: &[crate::piece::constants::PieceInfo; 12]) -> ArrForm<{ crate::notation::SAN_MAX_LEN }> {
+        crate::notation::to_san(self, board, pieces_info)
+    }
+
+    // Returns the legal move for a move in UCI long algebraic notation
+    pub fn from_uci(uci: &str, board: &board_representation::Board, pieces_info: &[crate::piece::constants::PieceInfo; 12]) -> Result<Self, crate::notation::MoveParseError> {
+        crate::notation::parse_uci(uci, board, pieces_info)
+    }
+
+    // Returns the legal move for a move in standard algebraic notation
+    pub fn from_san(san: &str, board: &board_representation::Board, pieces_info: &[crate::piece::constants::PieceInfo; 12]) -> Result<Self, crate::notation::MoveParseError> {
+        crate::notation::parse_san(san, board, pieces_info)
+    }
+
     pub fn flip(mut self) -> Self {
         self.initial_piece_coordinates.bit = crate::flip_bitboard_bit(self.initial_piece_coordinates.bit);
         self.final_piece_bit = crate::flip_bitboard_bit(self.final_piece_bit);
@@ -31,18 +73,40 @@ impl Move {
     }
 }
 
+// Search scores are in centipawns
+// Checkmates are scored as MATE_SCORE minus the number of half moves from the start of the search to the mate
+// So shorter mates are preferred, and the losing team prefers longer defences
+pub const INFINITY: i16 = 32000;
+pub const MATE_SCORE: i16 = 31000;
+pub const MAX_MATE_PLY: i16 = 1000; // Scores within this many half moves of MATE_SCORE are mate scores
+
+// Returns true if the score is a checkmate for either team
+pub fn is_mate_score(score: i16) -> bool {
+    score.abs() > MATE_SCORE - MAX_MATE_PLY
+}
+
+// Returns the number of full moves until checkmate for a mate score
+// Positive if the team the score belongs to gives mate, negative if it gets mated
+pub fn mate_in(score: i16) -> i16 {
+    if score > 0 {
+        (MATE_SCORE - score + 1) / 2
+    } else {
+        -(MATE_SCORE + score + 1) / 2
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub struct AlphaBeta {
-    pub alpha: i8,
-    pub beta: i8,
+    pub alpha: i16,
+    pub beta: i16,
     pub piece_move: Option<Move>,
 }
 
 impl AlphaBeta {
     pub fn new() -> Self {
         AlphaBeta {
-            alpha: i8::MIN, // -Infinity
-            beta: i8::MAX, // +Infinity
+            alpha: -INFINITY,
+            beta: INFINITY,
             piece_move: None,
         }
     }
@@ -64,33 +128,223 @@ pub fn update_alpha_beta(my_alpha_beta: &mut AlphaBeta, child_alpha_beta: &Alpha
     }
 }
 
+// Order values for each type of move
+// Captures and promotions are ordered by most valuable victim / least valuable attacker (MVV-LVA)
+// Quiet moves are ordered by their history value and the opening heatmap, which are kept between KILLER_ORDER and LOSING_CAPTURE_ORDER
+const TT_MOVE_ORDER: i16 = 30000;
+const PV_MOVE_ORDER: i16 = 29000;
+const CAPTURE_ORDER: i16 = 20000;
+const KILLER_ORDER: i16 = 18000;
+const LOSING_CAPTURE_ORDER: i16 = -20000;
+const MAX_QUIET_ORDER: i16 = 16000;
+
+// Number of half moves from the start of the search that killer moves are kept for
+pub const MAX_KILLER_PLY: usize = 16;
+
+// History values are halved once a value reaches this limit, so newer cutoffs have more effect than old ones
+const MAX_HISTORY_VALUE: i16 = 8000;
+
+// Quiet moves that caused beta cutoffs, used to order quiet moves
+// Killer moves are kept for each half move from the start of the search, since a move that refutes one branch is likely to refute its sibling branches
+// History values are kept for each piece and final bit, and are increased more by cutoffs deeper in the search
+pub struct MoveHistory {
+    killers: [[Option<(u8, u8)>; 2]; MAX_KILLER_PLY], // Initial and final bit of the two latest killer moves at each half move
+    history: [[i16; 64]; 12],
+}
+
+impl MoveHistory {
+    pub const fn new() -> Self {
+        MoveHistory {
+            killers: [[None; 2]; MAX_KILLER_PLY],
+            history: [[0; 64]; 12],
+        }
+    }
+
+    // Removes the killer moves and halves the history values
+    // Killer moves are for half moves from the start of the search, so they don't carry over to the next search
+    pub fn new_search(&mut self) {
+        self.killers = [[None; 2]; MAX_KILLER_PLY];
+        self.age_history();
+    }
+
+    // Removes all killer moves and history values
+    pub fn clear(&mut self) {
+        self.killers = [[None; 2]; MAX_KILLER_PLY];
+        self.history = [[0; 64]; 12];
+    }
+
+    // Returns the killer slot the move is in, 0 is the latest killer move
+    fn killer_slot(&self, ply: usize, piece_move: &Move) -> Option<usize> {
+        if ply >= MAX_KILLER_PLY {
+            return None;
+        }
+
+        let compact_move = (piece_move.initial_piece_coordinates.bit as u8, piece_move.final_piece_bit as u8);
+        self.killers[ply].iter().position(|&killer| killer == Some(compact_move))
+    }
+
+    // Records a quiet move that caused a beta cutoff, depth is the remaining search depth of the branch
+    fn update(&mut self, ply: usize, depth: usize, piece_move: &Move) {
+        if ply < MAX_KILLER_PLY {
+            let compact_move = Some((piece_move.initial_piece_coordinates.bit as u8, piece_move.final_piece_bit as u8));
+            if self.killers[ply][0] != compact_move {
+                self.killers[ply][1] = self.killers[ply][0];
+                self.killers[ply][0] = compact_move;
+            }
+        }
+
+        let history_value = &mut self.history[piece_move.initial_piece_coordinates.board_index][piece_move.final_piece_bit];
+        *history_value = history_value.saturating_add((depth * depth) as i16);
+        if *history_value >= MAX_HISTORY_VALUE {
+            self.age_history();
+        }
+    }
+
+    fn age_history(&mut self) {
+        for i in 0..12 {
+            for j in 0..64 {
+                self.history[i][j] /= 2;
+            }
+        }
+    }
+}
+
+// Maximum number of moves kept in the principal variation
+pub const MAX_PV_LEN: usize = 16;
+
+// Statistics and results of a search, updated while the search runs
+#[derive(Copy, Clone, Debug)]
+pub struct SearchInfo {
+    pub depth: usize, // Search depth of the deepest completed iteration
+    pub iteration_depth: usize, // Search depth of the iteration being searched
+    pub nodes: u32, // Number of positions searched, including quiescence positions
+    pub cutoffs: u32, // Number of alpha beta cutoffs
+    pub elapsed_cycles: u64, // Cycles from the start of the search to the end of the last iteration
+    pub score: i16, // Score of the deepest completed iteration, from the perspective of the team to move
+    pub pv: [Move; MAX_PV_LEN], // Principal variation, the line of moves the deepest completed iteration expects to be played
+    pub pv_len: usize,
+    pub stable_iterations: usize, // Number of completed iterations in a row that found the same best move
+    pub score_drop: i16, // How much the score of the deepest completed iteration dropped from the iteration before it
+    pub reductions: bool, // Null move pruning and late move reductions are used, only turned off to measure what they gain
+}
+
+impl SearchInfo {
+    pub fn new() -> Self {
+        SearchInfo {
+            depth: 0,
+            iteration_depth: 0,
+            nodes: 0,
+            cutoffs: 0,
+            elapsed_cycles: 0,
+            score: 0,
+            pv: [Move::new(); MAX_PV_LEN],
+            pv_len: 0,
+            stable_iterations: 0,
+            score_drop: 0,
+            reductions: true,
+        }
+    }
+
+    pub fn pv(&self) -> &[Move] {
+        &self.pv[..self.pv_len]
+    }
+}
+
+// Source of time for the search
+// Implemented by the DWT cycle counter on the chess board, and by a host clock when the engine runs on a computer
+pub trait CycleCounter {
+    // Reads the latest cycle count
+    fn update(&mut self);
+
+    // Returns the cycle count from the last update
+    fn cycles(&self) -> u64;
 
-pub fn gen_best_move(
+    // Called after each completed iteration of the search, e.g. to report the search progress
+    fn iteration_complete(&mut self, _search_info: &SearchInfo) {}
+}
+
+pub fn gen_best_move<const N: usize>(
     master_team: bool,
-    cycle_counter: &mut crate::embedded::cycle_counter::Counter,
+    cycle_counter: &mut impl CycleCounter,
     start_cycles: &u64,
-    max_elapsed_cycles: &u64,
+    time_limits: &TimeLimits,
     search_depth: usize,
     current_depth: usize,
-    init_value: i8,
     mut alpha_beta: AlphaBeta,
     opening_heatmap: &[[i16; 64]; 12],
+    eval_noise: &EvalNoise,
+    transposition_table: &mut TranspositionTable<N>,
+    move_history: &mut MoveHistory,
+    search_info: &mut SearchInfo,
     board: board_representation::Board,
     pieces_info: &[crate::piece::constants::PieceInfo; 12]
 ) -> AlphaBeta {
     use crate::board::move_generator;
     use crate::board::move_generator::TurnError;
 
-    // If current depth and search depth are equal stop searching down the move tree
-    // Or stop searching if the time elapsed is greater than the maximum allowed time
+    // If current depth and search depth are equal stop searching down the move tree and search captures until the board is quiet
+    if current_depth == search_depth {
+        return quiescence(master_team, current_depth, MAX_QUIESCENCE_DEPTH, alpha_beta, opening_heatmap, eval_noise, move_history, search_info, board, pieces_info);
+    }
+
+    // Stop searching if the time elapsed is greater than the maximum allowed time
+    // The root always searches its moves, so the first iteration finishes and the search always has a move to play
+    // Deeper iterations at the root stop after the iteration below them, since the hard limit is past the limit for starting iterations
     cycle_counter.update();
-    if current_depth == search_depth || cycle_counter.cycles > start_cycles + max_elapsed_cycles {
+    if current_depth > 0 && cycle_counter.cycles() > start_cycles + time_limits.hard_cycles {
+        let value = leaf_value(&board, master_team, eval_noise);
         return AlphaBeta {
-            alpha: init_value,
-            beta: init_value,
+            alpha: value,
+            beta: value,
             piece_move: None,
         };
     }
+    search_info.nodes += 1;
+
+    let mut init_alpha_beta = alpha_beta; // Alpha and beta before any moves are searched
+
+    // Look up the position in the transposition table
+    // Scores in the table are from the perspective of the team to move
+    let tt_entry = transposition_table.probe(board.key);
+    if let Some(entry) = tt_entry {
+
+        // Only use the score if the position was searched at least as deep as it would be now
+        // The root always searches so it has a move to return
+        if current_depth > 0 && entry.depth as usize >= search_depth - current_depth {
+            let mut score = entry.score;
+            let mut bound = entry.bound;
+
+            // Convert the score to the master team perspective
+            if !master_team {
+                score *= -1;
+                bound = match bound {
+                    Bound::Exact => Bound::Exact,
+                    Bound::Lower => Bound::Upper,
+                    Bound::Upper => Bound::Lower,
+                };
+            }
+
+            // Mate scores in the table count half moves from the stored position, convert them to count from the start of the search
+            let mut value = score;
+            if is_mate_score(value) {
+                value -= value.signum() * current_depth as i16;
+            }
+
+            let use_value = match bound {
+                Bound::Exact => true,
+                Bound::Lower => value >= alpha_beta.beta,
+                Bound::Upper => value <= alpha_beta.alpha,
+            };
+
+            if use_value {
+                return AlphaBeta {
+                    alpha: value,
+                    beta: value,
+                    piece_move: None,
+                };
+            }
+        }
+    }
 
     // Get friendly and enemy team BoardCoordinates
     let friendly_king_index;
@@ -118,9 +372,70 @@ pub fn gen_best_move(
 
     // Generate enemy attacks
     let enemy_attacks = move_generator::gen_enemy_attacks(&friendly_king, team_bitboards, &board, pieces_info);
+    let in_check = crate::bit_on(enemy_attacks.enemy_attack_bitboard, friendly_king.bit);
+
+    // Null move pruning
+    // If the score is still past beta after passing the turn then making a move would be too, so the branch can be cut off without searching any moves
+    // Not used in check, since passing would leave the king in check, or when the team to move only has pawns, since passing could be better than any move (zugzwang)
+    if search_info.reductions && current_depth > 0 && search_depth - current_depth > NULL_MOVE_REDUCTION && !in_check && has_pieces(&board) {
+        let value = leaf_value(&board, master_team, eval_noise);
+
+        // Only try passing when the score is already past beta
+        let past_beta;
+        let mut null_window = alpha_beta;
+        if master_team {
+            past_beta = value >= alpha_beta.beta;
+            null_window.alpha = alpha_beta.beta - 1;
+        } else {
+            past_beta = value <= alpha_beta.alpha;
+            null_window.beta = alpha_beta.alpha + 1;
+        }
+
+        if past_beta {
+            let null_alpha_beta = gen_best_move(
+                !master_team,
+                cycle_counter,
+                start_cycles,
+                time_limits,
+                search_depth - NULL_MOVE_REDUCTION,
+                current_depth + 1,
+                null_window,
+                opening_heatmap,
+                eval_noise,
+                transposition_table,
+                move_history,
+                search_info,
+                move_generator::null_turn(&board),
+                pieces_info
+            );
+
+            if master_team && null_alpha_beta.beta >= alpha_beta.beta {
+                search_info.cutoffs += 1;
+                alpha_beta.alpha = alpha_beta.beta;
+                return alpha_beta;
+            }
+            if !master_team && null_alpha_beta.alpha <= alpha_beta.alpha {
+                search_info.cutoffs += 1;
+                alpha_beta.beta = alpha_beta.alpha;
+                return alpha_beta;
+            }
+        }
+    }
 
     // Generate moves
-    let moves = &mut order_moves(true, &board, &enemy_attacks, &friendly_king, opening_heatmap, &team_bitboards, pieces_info);
+    // Moves aren't sorted, instead the best remaining move is picked before each move is searched
+    // So moves after a beta cutoff don't need to be sorted
+    let moves = &mut order_moves(false, &board, &enemy_attacks, &friendly_king, opening_heatmap, move_history, current_depth, &team_bitboards, pieces_info);
+
+    // Search the best move from the transposition table first
+    if let Some(entry) = tt_entry {
+        for i in 0..moves.len {
+            if entry.is_best_move(&moves.moves[i]) {
+                moves.moves[i].order_value = TT_MOVE_ORDER;
+                break;
+            }
+        }
+    }
 
     // Add pv move from lower search depth to the start of moves vec to increase alpha beta cuttoffs
     // Iterative deepening
@@ -130,112 +445,291 @@ pub fn gen_best_move(
             true,
             cycle_counter,
             start_cycles,
-            max_elapsed_cycles,
+            time_limits,
             search_depth - 1,
             0,
-            0,
             AlphaBeta::new(),
             opening_heatmap,
+            eval_noise,
+            transposition_table,
+            move_history,
+            search_info,
             board,
             pieces_info
         );
 
-        moves.rotate_right(1);
-        moves[0] = alpha_beta.piece_move.unwrap();
+        // Search the pv move first
+        if let Some(pv_move) = alpha_beta.piece_move {
+            for i in 0..moves.len {
+                if moves.moves[i].same_move(&pv_move) {
+                    moves.moves[i].order_value = PV_MOVE_ORDER;
+                    break;
+                }
+            }
+        }
         pv_alpha_beta = Some(alpha_beta);
+
+        // Don't start a deeper iteration after the time for starting iterations has been used, since it is unlikely to finish
+        if alpha_beta.piece_move.is_some() && cycle_counter.cycles() - start_cycles > time_limits.iteration_cycles(search_info) {
+            return alpha_beta;
+        }
     } else {
         pv_alpha_beta = None;
     }
 
-    for i in 0..moves.len() {
-        let initial_piece_coordinates = moves[i].initial_piece_coordinates;
-        let final_piece_bit = moves[i].final_piece_bit;
+    if current_depth == 0 {
+        search_info.iteration_depth = search_depth;
+    }
 
-        let new_turn_board = move_generator::new_turn(&initial_piece_coordinates, final_piece_bit, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, board, &pieces_info);
-        
-        match new_turn_board {
+    // Aspiration window
+    // The score is expected to be close to the score from the lower search depth, so the root is searched with a narrow window around it
+    // If the score is outside of the window the root is searched again with the full window
+    let full_alpha_beta = alpha_beta;
+    if let Some(pv_alpha_beta) = pv_alpha_beta {
+        if pv_alpha_beta.piece_move.is_some() && !is_mate_score(pv_alpha_beta.alpha) && cycle_counter.cycles() <= start_cycles + time_limits.hard_cycles {
+            alpha_beta.alpha = (pv_alpha_beta.alpha - ASPIRATION_WINDOW).max(full_alpha_beta.alpha);
+            alpha_beta.beta = (pv_alpha_beta.alpha + ASPIRATION_WINDOW).min(full_alpha_beta.beta);
+            init_alpha_beta = alpha_beta;
+        }
+    }
 
-            // Only continue searching down the move tree if the move didn't result in an invalid move or the end of the game
-            Ok(new_board) => {
-                let mut move_value = new_board.points_delta;
-                
-                // If the current branch is not the master team then it's move values are negative (because they negatively impact the master team)
-                if !master_team {
-                    move_value *= -1;
-                }
+    loop {
+        let mut searched_moves = 0; // Number of moves searched, moves after the first are searched with a null window
 
-                let branch_value = init_value + move_value;
+        for i in 0..moves.len {
+            pick_move(moves, i);
 
-                let mut child_alpha_beta = gen_best_move(
-                    !master_team,
-                    cycle_counter,
-                    start_cycles,
-                    max_elapsed_cycles,
-                    search_depth,
-                    current_depth + 1,
-                    branch_value,
-                    alpha_beta,
-                    opening_heatmap,
-                    new_board,
-                    pieces_info
-                );
+            let initial_piece_coordinates = moves.moves[i].initial_piece_coordinates;
+            let final_piece_bit = moves.moves[i].final_piece_bit;
+            let promotion = moves.moves[i].promotion;
 
-                let piece_move = Move {
-                    initial_piece_coordinates: initial_piece_coordinates,
-                    final_piece_bit: final_piece_bit,
-                    value: 0,
-                    heatmap_value: 0,
-                };
+            let new_turn_board = move_generator::new_turn(&initial_piece_coordinates, final_piece_bit, promotion, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, board, pieces_info);
+        
+            match new_turn_board {
 
-                child_alpha_beta.piece_move = Some(piece_move);
+                // Only continue searching down the move tree if the move didn't result in an invalid move or the end of the game
+                Ok(new_board) => {
+                    // Principal variation search
+                    // The first move searched is expected to be the best, so later moves are searched with a null window that only shows if they are better
+                    // Moves that are better are searched again with the full window to get their score
+                    let mut child_window = alpha_beta;
+                    let mut null_window = false;
+                    if searched_moves > 0 {
+                        if master_team {
+                            child_window.beta = alpha_beta.alpha + 1;
+                        } else {
+                            child_window.alpha = alpha_beta.beta - 1;
+                        }
+                        null_window = true;
+                    }
 
-                update_alpha_beta(&mut alpha_beta, &child_alpha_beta, master_team);
-            },
-            Err(error) => {
+                    // Check extensions
+                    // Moves that give check are searched one half move deeper, so forced mates just past the search depth are found
+                    // Each branch can only be extended a few times, to limit the stack space used by the search
+                    let gives_check = new_board.in_check();
+                    let mut full_search_depth = search_depth;
+                    if gives_check && search_depth < search_info.iteration_depth + MAX_CHECK_EXTENSIONS {
+                        full_search_depth += 1;
+                    }
 
-                // Update alpha/beta with value of game ending if the game ended
-                let mut branch_value;
-                let valid_move;
+                    // Late move reductions
+                    // Quiet moves late in the ordered list are unlikely to be the best move, so they are searched one half move less deep
+                    // Moves at the root are always searched to the full depth
+                    let mut child_search_depth = full_search_depth;
+                    if search_info.reductions && current_depth > 0 && searched_moves >= LATE_MOVE_REDUCTION_MOVES && search_depth - current_depth >= LATE_MOVE_REDUCTION_DEPTH && !in_check && !gives_check
+                        && moves.moves[i].order_value <= MAX_QUIET_ORDER && is_quiet(&moves.moves[i], &board, &team_bitboards) {
+                        child_search_depth -= 1;
+                    }
 
-                match error {
-                    TurnError::Win => {branch_value = i8::MAX; valid_move = true},
-                    TurnError::Draw => {branch_value = 0; valid_move = true},
-                    TurnError::InvalidMove => {branch_value = 0; valid_move = false},
-                    TurnError::InvalidMoveCheck => {branch_value = 0; valid_move = false},
-                }
+                    let mut child_alpha_beta;
+                    loop {
+                        child_alpha_beta = gen_best_move(
+                            !master_team,
+                            cycle_counter,
+                            start_cycles,
+                            time_limits,
+                            child_search_depth,
+                            current_depth + 1,
+                            child_window,
+                            opening_heatmap,
+                            eval_noise,
+                            transposition_table,
+                            move_history,
+                            search_info,
+                            new_board,
+                            pieces_info
+                        );
+
+                        let child_value;
+                        if master_team {
+                            child_value = child_alpha_beta.beta;
+                        } else {
+                            child_value = child_alpha_beta.alpha;
+                        }
 
-                if valid_move {
-                    // If the current branch is not the master team then it's move values are negative (because they negatively impact the master team)
-                    if !master_team {
-                        branch_value *= -1;
+                        // Reduced moves that are better than the best move so far are searched again at the full depth
+                        let better;
+                        if master_team {
+                            better = child_value > alpha_beta.alpha;
+                        } else {
+                            better = child_value < alpha_beta.beta;
+                        }
+                        if child_search_depth != full_search_depth && better {
+                            child_search_depth = full_search_depth;
+                            continue;
+                        }
+
+                        if null_window && child_value > alpha_beta.alpha && child_value < alpha_beta.beta {
+                            child_window = alpha_beta;
+                            null_window = false;
+                            continue;
+                        }
+                        break;
                     }
+                    searched_moves += 1;
 
                     let piece_move = Move {
                         initial_piece_coordinates: initial_piece_coordinates,
                         final_piece_bit: final_piece_bit,
+                        promotion: promotion,
                         value: 0,
-                        heatmap_value: 0,
+                        order_value: 0,
                     };
 
-                    let child_alpha_beta = AlphaBeta {
-                        alpha: branch_value,
-                        beta: branch_value,
-                        piece_move: Some(piece_move),
-                    };
+                    child_alpha_beta.piece_move = Some(piece_move);
 
                     update_alpha_beta(&mut alpha_beta, &child_alpha_beta, master_team);
+                },
+                Err(error) => {
+
+                    // Update alpha/beta with value of game ending if the game ended
+                    let mut branch_value;
+                    let valid_move;
+
+                    match error {
+                        TurnError::Win => {branch_value = MATE_SCORE - (current_depth as i16 + 1); valid_move = true},
+                        TurnError::Draw => {branch_value = 0; valid_move = true},
+                        TurnError::InvalidMove => {branch_value = 0; valid_move = false},
+                        TurnError::InvalidMoveCheck => {branch_value = 0; valid_move = false},
+                    }
+
+                    if valid_move {
+                        // If the current branch is not the master team then it's move values are negative (because they negatively impact the master team)
+                        if !master_team {
+                            branch_value *= -1;
+                        }
+
+                        let piece_move = Move {
+                            initial_piece_coordinates: initial_piece_coordinates,
+                            final_piece_bit: final_piece_bit,
+                            promotion: promotion,
+                            value: 0,
+                            order_value: 0,
+                        };
+
+                        let child_alpha_beta = AlphaBeta {
+                            alpha: branch_value,
+                            beta: branch_value,
+                            piece_move: Some(piece_move),
+                        };
+
+                        update_alpha_beta(&mut alpha_beta, &child_alpha_beta, master_team);
+                    }
+                },
+            }
+            // Stop searching this branch if alpha >= beta
+            // Quiet moves that cause a cutoff are remembered to order moves in other branches
+            if alpha_beta.alpha >= alpha_beta.beta {
+                search_info.cutoffs += 1;
+                if is_quiet(&moves.moves[i], &board, &team_bitboards) {
+                    move_history.update(current_depth, search_depth - current_depth, &moves.moves[i]);
                 }
-            },
+                break;
+            }
         }
-        // Stop searching this branch if alpha >= beta
+
+        // Search again with the full window if the score was outside of the aspiration window
+        let failed_low = alpha_beta.alpha <= init_alpha_beta.alpha;
+        let failed_high = alpha_beta.alpha >= alpha_beta.beta;
+        if init_alpha_beta != full_alpha_beta && (failed_low || failed_high) && cycle_counter.cycles() <= start_cycles + time_limits.hard_cycles {
+            alpha_beta = full_alpha_beta;
+            init_alpha_beta = full_alpha_beta;
+            continue;
+        }
+        break;
+    }
+
+    // Store the result in the transposition table
+    // Results are incomplete if the search ran out of time, so they aren't stored
+    if cycle_counter.cycles() <= start_cycles + time_limits.hard_cycles {
+        let value;
+        let improved; // True if a move improved on the alpha / beta that was given to this branch
+        if master_team {
+            value = alpha_beta.alpha;
+            improved = alpha_beta.alpha != init_alpha_beta.alpha;
+        } else {
+            value = alpha_beta.beta;
+            improved = alpha_beta.beta != init_alpha_beta.beta;
+        }
+
+        // Bounds are the same for the master team and the enemy team when the score is from the perspective of the team to move
+        let bound;
         if alpha_beta.alpha >= alpha_beta.beta {
-            break;
+            bound = Bound::Lower;
+        } else if !improved {
+            bound = Bound::Upper;
+        } else {
+            bound = Bound::Exact;
+        }
+
+        let mut score = value;
+        if !master_team {
+            score *= -1;
+        }
+
+        // Store mate scores as half moves from this position, so they are correct when the position is found at a different depth
+        if is_mate_score(score) {
+            score += score.signum() * current_depth as i16;
+        }
+
+        let best_move;
+        if improved {
+            best_move = alpha_beta.piece_move;
+        } else {
+            best_move = None;
+        }
+
+        transposition_table.store(board.key, search_depth - current_depth, bound, score, best_move);
+    }
+
+    // Record the results of each completed iteration, deeper iterations finish later so they replace the results of shallower ones
+    if current_depth == 0 {
+        search_info.elapsed_cycles = cycle_counter.cycles() - start_cycles;
+
+        // The first iteration is always recorded, since it finishes even after the hard limit
+        if cycle_counter.cycles() <= start_cycles + time_limits.hard_cycles || search_depth == 1 {
+            if let Some(piece_move) = alpha_beta.piece_move {
+                // Track how stable the best move and score are across iterations, for time management
+                if search_info.pv_len > 0 && search_info.pv[0].same_move(&piece_move) {
+                    search_info.stable_iterations += 1;
+                } else {
+                    search_info.stable_iterations = 0;
+                }
+                if search_info.depth > 0 {
+                    search_info.score_drop = search_info.score.saturating_sub(alpha_beta.alpha).max(0);
+                }
+
+                search_info.depth = search_depth;
+                search_info.score = alpha_beta.alpha;
+                update_pv(piece_move, search_depth, transposition_table, search_info, board, pieces_info);
+                cycle_counter.iteration_complete(search_info);
+            }
         }
     }
 
     // If the time exceeded the maximum allowed time return the pv move from a lower search depth
     if current_depth == 0 && search_depth > 1 {
-        if cycle_counter.cycles > start_cycles + max_elapsed_cycles {
+        if cycle_counter.cycles() > start_cycles + time_limits.hard_cycles {
             return pv_alpha_beta.unwrap();
         }
     }
@@ -243,116 +737,705 @@ pub fn gen_best_move(
     alpha_beta    
 }
 
-// Returns a vec with potential moves
-// If sort is true the moves will be ordered from best to worst
-// All moves are valid apart from king moves
-fn order_moves(sort: bool, board: &board_representation::Board, enemy_attacks: &EnemyAttacks, friendly_king: &board_representation::BoardCoordinates, opening_heatmap: &[[i16; 64]; 12], team_bitboards: &crate::TeamBitboards, pieces_info: &[crate::piece::constants::PieceInfo; 12]) -> [Move; 96]  {
+// Follows the best moves stored in the transposition table from the root to find the principal variation
+// The line ends early if a position has been replaced in the table, or a move ends the game
+fn update_pv<const N: usize>(
+    root_move: Move,
+    search_depth: usize,
+    transposition_table: &TranspositionTable<N>,
+    search_info: &mut SearchInfo,
+    mut board: board_representation::Board,
+    pieces_info: &[crate::piece::constants::PieceInfo; 12]
+) {
+    use crate::board::move_generator;
+
+    let max_len = search_depth.min(MAX_PV_LEN);
+
+    search_info.pv_len = 0;
+    let mut piece_move = Some(root_move);
+    while let Some(pv_move) = piece_move {
+        search_info.pv[search_info.pv_len] = pv_move;
+        search_info.pv_len += 1;
+
+        let (friendly_king, enemy_king) = board.kings();
+        let team_bitboards = TeamBitboards::new(friendly_king.board_index, &board);
+        let enemy_attacks = move_generator::gen_enemy_attacks(&friendly_king, team_bitboards, &board, pieces_info);
+
+        match move_generator::new_turn(&pv_move.initial_piece_coordinates, pv_move.final_piece_bit, pv_move.promotion, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, board, pieces_info) {
+            Ok(new_board) => board = new_board,
+            Err(_) => break,
+        }
+
+        if search_info.pv_len == max_len {
+            break;
+        }
+
+        // Find the stored best move in the legal moves so the move has the correct board index
+        piece_move = None;
+        if let Some(entry) = transposition_table.probe(board.key) {
+            let moves = move_generator::legal_moves(&board, pieces_info);
+            for i in 0..moves.len {
+                if entry.is_best_move(&moves.moves[i]) {
+                    piece_move = Some(moves.moves[i]);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// Evaluates the board for a leaf of the move tree, from the master team perspective
+// Evaluations (with the noise of weaker strength levels) are kept below mate scores
+fn leaf_value(board: &board_representation::Board, master_team: bool, eval_noise: &EvalNoise) -> i16 {
+    let mut evaluation = (crate::evaluation::evaluate(board) + eval_noise.value(board.key)).clamp(-(MATE_SCORE - MAX_MATE_PLY), MATE_SCORE - MAX_MATE_PLY);
+
+    // Evaluations are from the perspective of the team to move, which is the master team on master team branches
+    if !master_team {
+        evaluation *= -1;
+    }
+
+    evaluation
+}
+
+// Predicts the reply of the team to move from the best move stored in the transposition table
+// Returns the board after the predicted move so the position can be searched on the players time (pondering)
+// None is returned if there is no stored move, or the predicted move ends the game
+pub fn predict_reply<const N: usize>(
+    board: &board_representation::Board,
+    transposition_table: &TranspositionTable<N>,
+    pieces_info: &[crate::piece::constants::PieceInfo; 12]
+) -> Option<board_representation::Board> {
+    use crate::board::move_generator;
+
+    let entry = transposition_table.probe(board.key)?;
+
+    let moves = move_generator::legal_moves(board, pieces_info);
+    for i in 0..moves.len {
+        if entry.is_best_move(&moves.moves[i]) {
+            let (friendly_king, enemy_king) = board.kings();
+            let team_bitboards = TeamBitboards::new(friendly_king.board_index, board);
+            let enemy_attacks = move_generator::gen_enemy_attacks(&friendly_king, team_bitboards, board, pieces_info);
+
+            let piece_move = moves.moves[i];
+            return move_generator::new_turn(&piece_move.initial_piece_coordinates, piece_move.final_piece_bit, piece_move.promotion, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, *board, pieces_info).ok();
+        }
+    }
+
+    None
+}
+
+// Chooses a random move that scores within margin of the best move, so weaker strength levels don't always play the best move
+// Each legal move is searched to search_depth from the current board, so the scores are comparable
+// Moves are only scored until the hard time limit, moves that weren't scored in time can't be chosen
+// Returns None if there are no legal moves, or if no move was scored within the hard time limit
+pub fn weaker_move<const N: usize>(
+    margin: i16,
+    search_depth: usize,
+    random: &mut Random,
+    cycle_counter: &mut impl CycleCounter,
+    start_cycles: &u64,
+    time_limits: &TimeLimits,
+    opening_heatmap: &[[i16; 64]; 12],
+    eval_noise: &EvalNoise,
+    transposition_table: &mut TranspositionTable<N>,
+    move_history: &mut MoveHistory,
+    board: &board_representation::Board,
+    pieces_info: &[crate::piece::constants::PieceInfo; 12]
+) -> Option<Move> {
+    use crate::board::move_generator;
+    use crate::board::move_generator::TurnError;
+
+    let (friendly_king, enemy_king) = board.kings();
+    let team_bitboards = TeamBitboards::new(friendly_king.board_index, board);
+    let enemy_attacks = move_generator::gen_enemy_attacks(&friendly_king, team_bitboards, board, pieces_info);
+
+    // Score each move from the perspective of the team to move
+    let mut moves = move_generator::legal_moves(board, pieces_info);
+    let mut best_score = -INFINITY;
+    let mut scored_moves = 0; // Moves before this index have been scored
+    for i in 0..moves.len {
+        cycle_counter.update();
+        if cycle_counter.cycles() > start_cycles + time_limits.hard_cycles {
+            break;
+        }
+
+        let piece_move = moves.moves[i];
+
+        let score;
+        match move_generator::new_turn(&piece_move.initial_piece_coordinates, piece_move.final_piece_bit, piece_move.promotion, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, *board, pieces_info) {
+            Ok(new_board) => {
+                let mut search_info = SearchInfo::new();
+                search_info.iteration_depth = search_depth;
+
+                // Searched the same way as a move at the root of gen_best_move, the enemy team moves next
+                score = gen_best_move(
+                    false,
+                    cycle_counter,
+                    start_cycles,
+                    time_limits,
+                    search_depth.max(1),
+                    1,
+                    AlphaBeta::new(),
+                    opening_heatmap,
+                    eval_noise,
+                    transposition_table,
+                    move_history,
+                    &mut search_info,
+                    new_board,
+                    pieces_info
+                ).beta;
+            },
+            Err(TurnError::Win) => score = MATE_SCORE - 1,
+            Err(_) => score = 0,
+        }
+
+        // The score of a search that ran out of time is incomplete
+        cycle_counter.update();
+        if cycle_counter.cycles() > start_cycles + time_limits.hard_cycles {
+            break;
+        }
+
+        moves.moves[i].order_value = score;
+        best_score = best_score.max(score);
+        scored_moves += 1;
+    }
+
+    if scored_moves == 0 {
+        return None;
+    }
+
+    // Mates aren't given away, so only the best moves are chosen when there is a mate
+    let min_score;
+    if is_mate_score(best_score) {
+        min_score = best_score;
+    } else {
+        min_score = best_score - margin;
+    }
+
+    let mut candidates = 0;
+    for i in 0..scored_moves {
+        if moves.moves[i].order_value >= min_score {
+            candidates += 1;
+        }
+    }
+
+    let mut chosen = random.below(candidates);
+    for i in 0..scored_moves {
+        if moves.moves[i].order_value >= min_score {
+            if chosen == 0 {
+                return Some(moves.moves[i]);
+            }
+            chosen -= 1;
+        }
+    }
+
+    None
+}
+
+// Number of half moves less that the search after a null move (passing the turn) searches
+const NULL_MOVE_REDUCTION: usize = 2;
+
+// Quiet moves are reduced after this many moves have been searched, in branches with at least LATE_MOVE_REDUCTION_DEPTH half moves left to search
+const LATE_MOVE_REDUCTION_MOVES: usize = 3;
+const LATE_MOVE_REDUCTION_DEPTH: usize = 3;
+
+// Maximum number of check extensions in a branch
+const MAX_CHECK_EXTENSIONS: usize = 2;
+
+// Half width of the aspiration window in centipawns
+const ASPIRATION_WINDOW: i16 = 50;
+
+// Maximum number of half moves searched by quiescence search after the search depth is reached
+// Each half move uses stack space, so this is kept small for the chess board
+pub const MAX_QUIESCENCE_DEPTH: usize = 4;
+
+// Maximum number of moves searched at each quiescence search branch
+const MAX_CAPTURES: usize = 24;
+
+// Searches captures and promotions until the board is quiet, so boards aren't evaluated in the middle of a capture sequence
+// The team to move can stand pat (not make a capture) if the evaluation of the board is allready good enough
+// Ply is the number of half moves from the start of the search
+fn quiescence(
+    master_team: bool,
+    ply: usize,
+    quiescence_depth: usize,
+    mut alpha_beta: AlphaBeta,
+    opening_heatmap: &[[i16; 64]; 12],
+    eval_noise: &EvalNoise,
+    move_history: &MoveHistory,
+    search_info: &mut SearchInfo,
+    board: board_representation::Board,
+    pieces_info: &[crate::piece::constants::PieceInfo; 12]
+) -> AlphaBeta {
+    use crate::board::move_generator;
+    use crate::board::move_generator::TurnError;
+
+    search_info.nodes += 1;
+
+    // Stand pat
+    let stand_pat = leaf_value(&board, master_team, eval_noise);
+    let stand_pat_alpha_beta = AlphaBeta {
+        alpha: stand_pat,
+        beta: stand_pat,
+        piece_move: None,
+    };
+    update_alpha_beta(&mut alpha_beta, &stand_pat_alpha_beta, master_team);
+
+    if alpha_beta.alpha >= alpha_beta.beta || quiescence_depth == 0 {
+        return alpha_beta;
+    }
+
+    let (friendly_king, enemy_king) = board.kings();
+    let team_bitboards = TeamBitboards::new(friendly_king.board_index, &board);
+    let enemy_attacks = move_generator::gen_enemy_attacks(&friendly_king, team_bitboards, &board, pieces_info);
+
+    let (captures, captures_len) = order_captures(&board, &enemy_attacks, &friendly_king, opening_heatmap, move_history, ply, &team_bitboards, pieces_info);
+
+    for i in 0..captures_len {
+        let piece_move = captures[i];
+        let new_turn_board = move_generator::new_turn(&piece_move.initial_piece_coordinates, piece_move.final_piece_bit, piece_move.promotion, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, board, pieces_info);
+
+        let child_alpha_beta;
+        match new_turn_board {
+            Ok(new_board) => child_alpha_beta = quiescence(!master_team, ply + 1, quiescence_depth - 1, alpha_beta, opening_heatmap, eval_noise, move_history, search_info, new_board, pieces_info),
+            Err(error) => {
+                let mut branch_value;
+                match error {
+                    TurnError::Win => branch_value = MATE_SCORE - (ply as i16 + 1),
+                    TurnError::Draw => branch_value = 0,
+                    TurnError::InvalidMove | TurnError::InvalidMoveCheck => continue,
+                }
+
+                if !master_team {
+                    branch_value *= -1;
+                }
+
+                child_alpha_beta = AlphaBeta {
+                    alpha: branch_value,
+                    beta: branch_value,
+                    piece_move: None,
+                };
+            },
+        }
+        update_alpha_beta(&mut alpha_beta, &child_alpha_beta, master_team);
+
+        // Stop searching this branch if alpha >= beta
+        if alpha_beta.alpha >= alpha_beta.beta {
+            search_info.cutoffs += 1;
+            break;
+        }
+    }
+
+    alpha_beta
+}
+
+// Returns the captures and queen promotions for the team to move, ordered from best to worst
+// Captures that are likely to lose material are left out
+// Not inlined so the full move list isn't kept in the stack frame of every quiescence search branch
+#[inline(never)]
+fn order_captures(board: &board_representation::Board, enemy_attacks: &EnemyAttacks, friendly_king: &board_representation::BoardCoordinates, opening_heatmap: &[[i16; 64]; 12], move_history: &MoveHistory, ply: usize, team_bitboards: &crate::TeamBitboards, pieces_info: &[crate::piece::constants::PieceInfo; 12]) -> ([Move; MAX_CAPTURES], usize) {
+    let moves = order_moves(true, board, enemy_attacks, friendly_king, opening_heatmap, move_history, ply, team_bitboards, pieces_info);
+
+    let mut captures = [Move::new(); MAX_CAPTURES];
+    let mut captures_len = 0;
+    for piece_move in moves.as_slice() {
+        if captures_len == MAX_CAPTURES {
+            break;
+        }
+
+        let capture = is_capture(piece_move, board, team_bitboards);
+        let queen_promotion = piece_move.promotion.is_some_and(|index| index % 6 == 4);
+
+        if (capture || queen_promotion) && piece_move.value >= 0 {
+            captures[captures_len] = *piece_move;
+            captures_len += 1;
+        }
+    }
+
+    (captures, captures_len)
+}
+
+// Returns true if the team to move has pieces other than pawns and the king
+fn has_pieces(board: &board_representation::Board) -> bool {
+    let first_index;
+    if board.whites_move {
+        first_index = 1;
+    } else {
+        first_index = 7;
+    }
+
+    for i in first_index..(first_index + 4) {
+        if board.board[i] != 0 {
+            return true;
+        }
+    }
+    false
+}
+
+// Returns true if the move captures an enemy piece, including en passant captures
+fn is_capture(piece_move: &Move, board: &board_representation::Board, team_bitboards: &crate::TeamBitboards) -> bool {
+    let board_index = piece_move.initial_piece_coordinates.board_index;
+    let pawn = board_index == 0 || board_index == 6;
+    crate::bit_on(team_bitboards.enemy_team, piece_move.final_piece_bit) || (pawn && board.en_passant_target == Some(piece_move.final_piece_bit))
+}
+
+// Returns true if the move doesn't capture or promote
+fn is_quiet(piece_move: &Move, board: &board_representation::Board, team_bitboards: &crate::TeamBitboards) -> bool {
+    piece_move.promotion.is_none() && !is_capture(piece_move, board, team_bitboards)
+}
+
+// Swaps the move with the highest order value from index onwards into index
+fn pick_move(moves: &mut MoveList<MAX_SEARCH_MOVES>, index: usize) {
+    let mut best_index = index;
+    for i in (index + 1)..moves.len {
+        if moves.moves[i].order_value > moves.moves[best_index].order_value {
+            best_index = i;
+        }
+    }
+    moves.moves.swap(index, best_index);
+}
+
+// Returns a list of potential moves
+// If sort is true the moves will be ordered from best to worst, otherwise moves can be picked in order with pick_move
+// Ply is the number of half moves from the start of the search, used to find killer moves
+// All moves are semi legal, new_turn has to be used to check if a move is legal
+fn order_moves(sort: bool, board: &board_representation::Board, enemy_attacks: &EnemyAttacks, friendly_king: &board_representation::BoardCoordinates, opening_heatmap: &[[i16; 64]; 12], move_history: &MoveHistory, ply: usize, team_bitboards: &crate::TeamBitboards, pieces_info: &[crate::piece::constants::PieceInfo; 12]) -> MoveList<MAX_SEARCH_MOVES>  {
     use crate::bit_on;
-    
-    let mut moves_index = 0;
-    let mut moves: [Move; 96] = [Move::new(); 96];
 
-    // Get friendly and enemy board indexes
-    let friendly_indexes;
+    let mut moves = crate::board::move_generator::gen_moves(friendly_king, enemy_attacks, team_bitboards, board, pieces_info);
+
+    // Get enemy board indexes
     let enemy_index_bottom; // Inclusive
     let enemy_index_top; // Not inclusive
     if board.whites_move {
-        friendly_indexes = 0..6;
         enemy_index_bottom = 6;
         enemy_index_top = 12;
     } else {
-        friendly_indexes = 6..12;
         enemy_index_bottom = 0;
         enemy_index_top = 6;
     }
 
-    for i in friendly_indexes {
+    for piece_move in moves.as_mut_slice() {
+        let i = piece_move.initial_piece_coordinates.board_index;
+        let initial_bit = piece_move.initial_piece_coordinates.bit;
+        let final_bit = piece_move.final_piece_bit;
         let piece_value = pieces_info[i].value;
 
-        for initial_bit in 0..64 {
-            let initial_piece_coordinates = board_representation::BoardCoordinates {
-                board_index: i,
-                bit: initial_bit,
-            };
-
-            // If there is no piece on the board at this bit go to the next bit
-            if !bit_on(board.board[i], initial_bit) {
-                continue;
-            }
+        // Value of the captured piece, en passant captures a pawn
+        let mut victim_value = 0;
+        if is_capture(piece_move, board, team_bitboards) {
+            victim_value = 1;
+        }
 
-            let piece_moves = crate::board::move_generator::gen_piece(&initial_piece_coordinates, None, team_bitboards, false, board, pieces_info);
-            
-            for final_bit in 0..64 {
+        // Get value of move based on value of captured piece
+        if bit_on(team_bitboards.enemy_team, final_bit) { // If an enemy piece is in the same bit as the friendly pieces final_bit then it has been captured
 
-                // The piece cannot move to final_bit if it is occupied by a friendly piece
-                if bit_on(team_bitboards.friendly_team, final_bit) {
-                    continue;
-                }
+            for j in enemy_index_bottom..enemy_index_top {
+                if bit_on(board.board[j], final_bit) {
+                    let capture_value = pieces_info[j].value;
+                    victim_value = capture_value;
 
-                // Get the heatmap value as the difference of the final and initial bit values
-                // This is to prevent pieces from moving to less advantageous positions than ones they are allready in
-                let heatmap_value = opening_heatmap[i][final_bit] - opening_heatmap[i][initial_bit];
+                    // If an enemy can move to the captured square there will likely be a trade
+                    if bit_on(enemy_attacks.enemy_attack_bitboard, final_bit) {
+                        piece_move.value = capture_value - piece_value;
+                    } else { // If an enemy can't move to the captured square then the friendly team gets the entire value of the captured piece
+                        piece_move.value = capture_value;
+                    }
 
-                // Check the piece can move to final_bit
-                if bit_on(piece_moves.moves_bitboard, final_bit) {
-                    
-                    // Get value of move based on value of captured piece
-                    let mut move_value = 0;
-                    if bit_on(team_bitboards.enemy_team, final_bit) { // If an enemy piece is in the same bit as the friendly pieces final_bit then it has been captured
+                    // Once the piece that has been captured is found break the loop
+                    break;
+                }
+            }
+        }
 
-                        for j in enemy_index_bottom..enemy_index_top {
-                            if bit_on(board.board[j], final_bit) {
-                                let capture_value = pieces_info[j].value;
-    
-                                // If an enemy can move to the captured square there will likely be a trade
-                                if bit_on(enemy_attacks.enemy_attack_bitboard, final_bit) {
-                                    move_value = piece_value - capture_value;
-                                } else { // If an enemy can't move to the captured square then the friendly team gets the entire value of the captured piece
-                                    move_value = capture_value;
-                                }
-    
-                                // Once the piece that has been captured is found break the loop
-                                break;
-                            }
-                        }
-                    }
+        // Add the value gained by promoting a pawn
+        if let Some(promotion_index) = piece_move.promotion {
+            piece_move.value += pieces_info[promotion_index].value - piece_value;
+            victim_value += pieces_info[promotion_index].value - piece_value;
+        }
 
-                    // Push move to moves vec
-                    moves[moves_index] = Move {
-                        initial_piece_coordinates: initial_piece_coordinates,
-                        final_piece_bit: final_bit,
-                        value: move_value,
-                        heatmap_value: heatmap_value,
-                    };
-                    moves_index += 1;
-                } else if &initial_piece_coordinates == friendly_king && (final_bit as i8 - initial_bit as i8).abs() == 2 {
+        if is_quiet(piece_move, board, team_bitboards) {
+            match move_history.killer_slot(ply, piece_move) {
+                Some(slot) => piece_move.order_value = KILLER_ORDER - slot as i16,
+                None => {
+                    // Get the heatmap value as the difference of the final and initial bit values
+                    // This is to prevent pieces from moving to less advantageous positions than ones they are allready in
+                    let heatmap_value = opening_heatmap[i][final_bit] - opening_heatmap[i][initial_bit];
+                    let history_value = move_history.history[i][final_bit];
+                    piece_move.order_value = history_value.saturating_add(heatmap_value).clamp(-MAX_QUIET_ORDER, MAX_QUIET_ORDER);
+                },
+            }
+        } else {
+            // Most valuable victim first, then least valuable attacker
+            let mvv_lva = victim_value as i16 * 10 - piece_value as i16;
 
-                    // If the piece can't move to the final bit, but is a king then add potential castling moves moves
-                    // Because king castling moves aren't a part of gen_piece, so they cannot be ruled out
-                    moves[moves_index] = Move {
-                        initial_piece_coordinates: initial_piece_coordinates,
-                        final_piece_bit: final_bit,
-                        value: 0,
-                        heatmap_value: heatmap_value,
-                    };
-                    moves_index += 1;
-                }
+            // Captures that are likely to lose material are searched after quiet moves
+            if piece_move.value >= 0 {
+                piece_move.order_value = CAPTURE_ORDER + mvv_lva;
+            } else {
+                piece_move.order_value = LOSING_CAPTURE_ORDER + mvv_lva;
             }
         }
     }
 
     // Sort moves and return
     if sort {
+        moves.as_mut_slice().sort_unstable_by_key(|piece_move| core::cmp::Reverse(piece_move.order_value));
+    }
+    moves
+}
 
-        // Sort moves by value first
-        // Sort moves by heatmap_value if they have the same value
-        // https://stackoverflow.com/questions/70193935/how-to-sort-a-vec-of-structs-by-2-or-multiple-fields
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::board_representation::Board;
 
-        moves.sort_unstable_by(| a, b | if a.value == b.value {
-            b.heatmap_value.partial_cmp(&a.heatmap_value).unwrap()
-        } else {
-            b.value.partial_cmp(&a.value).unwrap()
-        });
+    // Counts updates instead of clock cycles so searches are repeatable on a host machine
+    struct UpdateCounter {
+        cycles: u64,
     }
-    moves
-}
\ No newline at end of file
+
+    impl CycleCounter for UpdateCounter {
+        fn update(&mut self) {
+            self.cycles += 1;
+        }
+
+        fn cycles(&self) -> u64 {
+            self.cycles
+        }
+    }
+
+    fn search(fen: &str, search_depth: usize) -> AlphaBeta {
+        let pieces_info = crate::piece::constants::gen();
+        let board = Board::from_fen(fen).unwrap();
+        let mut cycle_counter = UpdateCounter { cycles: 0 };
+
+        gen_best_move(true, &mut cycle_counter, &0, &TimeLimits::fixed(u32::MAX.into()), search_depth, 0, AlphaBeta::new(), &[[0; 64]; 12], &EvalNoise::NONE, &mut TranspositionTable::<1024>::new(), &mut MoveHistory::new(), &mut SearchInfo::new(), board, &pieces_info)
+    }
+
+    fn best_move(fen: &str, search_depth: usize) -> Move {
+        search(fen, search_depth).piece_move.unwrap()
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let piece_move = best_move("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 3);
+        assert_eq!(crate::bit_to_ccn(piece_move.initial_piece_coordinates.bit), "a1");
+        assert_eq!(crate::bit_to_ccn(piece_move.final_piece_bit), "a8");
+    }
+
+    #[test]
+    fn captures_hanging_queen() {
+        let piece_move = best_move("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1", 2);
+        assert_eq!(crate::bit_to_ccn(piece_move.initial_piece_coordinates.bit), "d1");
+        assert_eq!(crate::bit_to_ccn(piece_move.final_piece_bit), "d5");
+    }
+
+    #[test]
+    fn finds_knight_promotion_mate() {
+        let piece_move = best_move("rb6/kpP5/pp6/8/8/8/8/7K w - - 0 1", 3);
+        assert_eq!(crate::bit_to_ccn(piece_move.initial_piece_coordinates.bit), "c7");
+        assert_eq!(crate::bit_to_ccn(piece_move.final_piece_bit), "c8");
+        assert_eq!(piece_move.promotion, Some(2));
+    }
+
+    #[test]
+    fn transposition_table_reuses_searches() {
+        let pieces_info = crate::piece::constants::gen();
+        let board = Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let mut transposition_table = TranspositionTable::<1024>::new();
+
+        let mut first_counter = UpdateCounter { cycles: 0 };
+        let first_search = gen_best_move(true, &mut first_counter, &0, &TimeLimits::fixed(u32::MAX.into()), 3, 0, AlphaBeta::new(), &[[0; 64]; 12], &EvalNoise::NONE, &mut transposition_table, &mut MoveHistory::new(), &mut SearchInfo::new(), board, &pieces_info);
+        assert!(transposition_table.probe(board.key).is_some());
+
+        // Searching the same position again uses the stored results, so fewer positions are searched
+        let mut second_counter = UpdateCounter { cycles: 0 };
+        let second_search = gen_best_move(true, &mut second_counter, &0, &TimeLimits::fixed(u32::MAX.into()), 3, 0, AlphaBeta::new(), &[[0; 64]; 12], &EvalNoise::NONE, &mut transposition_table, &mut MoveHistory::new(), &mut SearchInfo::new(), board, &pieces_info);
+        assert!(second_counter.cycles < first_counter.cycles);
+        assert_eq!(second_search.alpha, first_search.alpha);
+    }
+
+    #[test]
+    fn predicts_searched_reply() {
+        let pieces_info = crate::piece::constants::gen();
+        let board = Board::from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap();
+        let mut transposition_table = TranspositionTable::<1024>::new();
+
+        assert_eq!(predict_reply(&board, &transposition_table, &pieces_info), None);
+
+        let mut cycle_counter = UpdateCounter { cycles: 0 };
+        gen_best_move(true, &mut cycle_counter, &0, &TimeLimits::fixed(u32::MAX.into()), 2, 0, AlphaBeta::new(), &[[0; 64]; 12], &EvalNoise::NONE, &mut transposition_table, &mut MoveHistory::new(), &mut SearchInfo::new(), board, &pieces_info);
+
+        let predicted_board = predict_reply(&board, &transposition_table, &pieces_info).unwrap();
+        assert_eq!(predicted_board.key, Board::from_fen("4k3/8/8/3R4/8/8/8/4K3 b - - 0 1").unwrap().key);
+    }
+
+    fn ordered_moves(fen: &str, move_history: &MoveHistory) -> MoveList<MAX_SEARCH_MOVES> {
+        let pieces_info = crate::piece::constants::gen();
+        let board = Board::from_fen(fen).unwrap();
+        let (friendly_king, _) = board.kings();
+        let team_bitboards = TeamBitboards::new(friendly_king.board_index, &board);
+        let enemy_attacks = crate::board::move_generator::gen_enemy_attacks(&friendly_king, team_bitboards, &board, &pieces_info);
+
+        order_moves(true, &board, &enemy_attacks, &friendly_king, &[[0; 64]; 12], move_history, 0, &team_bitboards, &pieces_info)
+    }
+
+    #[test]
+    fn orders_captures_by_mvv_lva() {
+        let moves = ordered_moves("4k3/8/8/2r1q3/3P4/8/8/3QK3 w - - 0 1", &MoveHistory::new());
+
+        // Pawn takes queen before pawn takes rook
+        let order = [("d4", "e5"), ("d4", "c5")];
+        for i in 0..order.len() {
+            assert_eq!(crate::bit_to_ccn(moves.moves[i].initial_piece_coordinates.bit), order[i].0);
+            assert_eq!(crate::bit_to_ccn(moves.moves[i].final_piece_bit), order[i].1);
+        }
+    }
+
+    #[test]
+    fn orders_killer_moves_before_quiet_moves() {
+        let fen = "4k3/8/8/2r5/3P4/8/8/3QK3 w - - 0 1";
+        let mut move_history = MoveHistory::new();
+        let killer_move = ordered_moves(fen, &move_history).moves[10];
+        move_history.update(0, 4, &killer_move);
+
+        // The capture is still searched first
+        let moves = ordered_moves(fen, &move_history);
+        assert_eq!(crate::bit_to_ccn(moves.moves[0].final_piece_bit), "c5");
+        assert!(moves.moves[1].same_move(&killer_move));
+
+        // Killer moves are removed for the next search but history values are kept
+        move_history.new_search();
+        assert_eq!(move_history.killer_slot(0, &killer_move), None);
+        assert!(ordered_moves(fen, &move_history).moves[1].same_move(&killer_move));
+    }
+
+    #[test]
+    fn prefers_shortest_mate() {
+        let alpha_beta = search("7k/8/6K1/8/8/8/8/Q7 w - - 0 1", 4);
+        let piece_move = alpha_beta.piece_move.unwrap();
+        assert_eq!(crate::bit_to_ccn(piece_move.initial_piece_coordinates.bit), "a1");
+        assert_eq!(crate::bit_to_ccn(piece_move.final_piece_bit), "a8");
+        assert_eq!(alpha_beta.alpha, MATE_SCORE - 1);
+        assert_eq!(mate_in(alpha_beta.alpha), 1);
+    }
+
+    #[test]
+    fn scores_mate_in_two() {
+        let alpha_beta = search("2r3k1/5ppp/8/8/8/8/4R3/4R1K1 w - - 0 1", 4);
+        assert_eq!(alpha_beta.alpha, MATE_SCORE - 3);
+        assert_eq!(mate_in(alpha_beta.alpha), 2);
+    }
+
+    #[test]
+    fn researches_outside_aspiration_window() {
+        // The mate is first found at depth 3, which is far above the aspiration window from the depth 2 score
+        let alpha_beta = search("7k/8/5K2/8/8/8/8/R7 w - - 0 1", 3);
+        assert_eq!(alpha_beta.alpha, MATE_SCORE - 3);
+    }
+
+    #[test]
+    fn extends_checks() {
+        // Qb8+ Kh7 Qh2# is only found at depth 2 because the check is searched one half move deeper
+        let alpha_beta = search("7k/Q4K2/8/8/8/8/8/8 w - - 0 1", 2);
+        assert_eq!(crate::bit_to_ccn(alpha_beta.piece_move.unwrap().final_piece_bit), "b8");
+        assert_eq!(alpha_beta.alpha, MATE_SCORE - 3);
+    }
+
+    #[test]
+    fn search_info_has_mating_line() {
+        let pieces_info = crate::piece::constants::gen();
+        let board = Board::from_fen("2r3k1/5ppp/8/8/8/8/4R3/4R1K1 w - - 0 1").unwrap();
+        let mut cycle_counter = UpdateCounter { cycles: 0 };
+        let mut search_info = SearchInfo::new();
+
+        let alpha_beta = gen_best_move(true, &mut cycle_counter, &0, &TimeLimits::fixed(u32::MAX.into()), 4, 0, AlphaBeta::new(), &[[0; 64]; 12], &EvalNoise::NONE, &mut TranspositionTable::<1024>::new(), &mut MoveHistory::new(), &mut search_info, board, &pieces_info);
+        assert_eq!(search_info.depth, 4);
+        assert_eq!(search_info.score, alpha_beta.alpha);
+        assert!(search_info.nodes > 0 && search_info.cutoffs > 0);
+        assert_eq!(search_info.elapsed_cycles, cycle_counter.cycles);
+
+        // Re8+ Rxe8 Rxe8#
+        let pv = search_info.pv();
+        assert_eq!(pv.len(), 3);
+        assert!(pv[0].same_move(&alpha_beta.piece_move.unwrap()));
+        assert_eq!(crate::bit_to_ccn(pv[1].initial_piece_coordinates.bit), "c8");
+        assert_eq!(crate::bit_to_ccn(pv[2].final_piece_bit), "e8");
+        assert_eq!(search_info.stable_iterations, 3);
+    }
+
+    #[test]
+    fn stops_deepening_after_soft_limit() {
+        let pieces_info = crate::piece::constants::gen();
+        let board = Board::from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap();
+        let mut cycle_counter = UpdateCounter { cycles: 0 };
+        let mut search_info = SearchInfo::new();
+        let time_limits = TimeLimits { soft_cycles: 1, hard_cycles: u32::MAX.into() };
+
+        // The first iteration is within the soft limit, no deeper iteration is started after it
+        let alpha_beta = gen_best_move(true, &mut cycle_counter, &0, &time_limits, 4, 0, AlphaBeta::new(), &[[0; 64]; 12], &EvalNoise::NONE, &mut TranspositionTable::<1024>::new(), &mut MoveHistory::new(), &mut search_info, board, &pieces_info);
+        assert_eq!(search_info.depth, 1);
+        assert_eq!(crate::bit_to_ccn(alpha_beta.piece_move.unwrap().final_piece_bit), "d5");
+    }
+
+    #[test]
+    fn finishes_first_iteration_after_hard_limit() {
+        let pieces_info = crate::piece::constants::gen();
+        let board = Board::from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap();
+        let mut cycle_counter = UpdateCounter { cycles: 0 };
+        let mut search_info = SearchInfo::new();
+        let time_limits = TimeLimits { soft_cycles: 0, hard_cycles: 0 };
+
+        // The first iteration finishes even though every update is past the hard limit, deeper iterations are stopped
+        let alpha_beta = gen_best_move(true, &mut cycle_counter, &0, &time_limits, 4, 0, AlphaBeta::new(), &[[0; 64]; 12], &EvalNoise::NONE, &mut TranspositionTable::<1024>::new(), &mut MoveHistory::new(), &mut search_info, board, &pieces_info);
+        assert_eq!(search_info.depth, 1);
+        assert_eq!(crate::bit_to_ccn(alpha_beta.piece_move.unwrap().final_piece_bit), "d5");
+    }
+
+    #[test]
+    fn scores_getting_mated() {
+        let alpha_beta = search("7k/8/6K1/8/8/8/8/Q7 b - - 0 1", 4);
+        assert_eq!(alpha_beta.alpha, -(MATE_SCORE - 2));
+        assert_eq!(mate_in(alpha_beta.alpha), -1);
+    }
+
+    #[test]
+    fn quiescence_avoids_defended_pawn() {
+        let piece_move = best_move("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1", 1);
+        assert!(crate::bit_to_ccn(piece_move.final_piece_bit) != "d5");
+    }
+
+    #[test]
+    fn weaker_move_stays_within_margin() {
+        let pieces_info = crate::piece::constants::gen();
+        let board = Board::from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap();
+        let mut transposition_table = TranspositionTable::<1024>::new();
+        let mut cycle_counter = UpdateCounter { cycles: 0 };
+
+        // Without a margin only the queen capture can be chosen
+        let mut weaker_moves = 0;
+        for seed in 1..20 {
+            let piece_move = weaker_move(0, 2, &mut Random::new(seed), &mut cycle_counter, &0, &TimeLimits::fixed(u32::MAX.into()), &[[0; 64]; 12], &EvalNoise::NONE, &mut transposition_table, &mut MoveHistory::new(), &board, &pieces_info).unwrap();
+            assert_eq!(crate::bit_to_ccn(piece_move.final_piece_bit), "d5");
+
+            let piece_move = weaker_move(2000, 2, &mut Random::new(seed), &mut cycle_counter, &0, &TimeLimits::fixed(u32::MAX.into()), &[[0; 64]; 12], &EvalNoise::NONE, &mut transposition_table, &mut MoveHistory::new(), &board, &pieces_info).unwrap();
+            if crate::bit_to_ccn(piece_move.final_piece_bit) != "d5" {
+                weaker_moves += 1;
+            }
+        }
+        assert!(weaker_moves > 0);
+    }
+
+    #[test]
+    fn weaker_move_stops_at_hard_limit() {
+        let pieces_info = crate::piece::constants::gen();
+        let board = Board::from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap();
+        let mut cycle_counter = UpdateCounter { cycles: 0 };
+        let time_limits = TimeLimits { soft_cycles: 0, hard_cycles: 0 };
+
+        // No move is scored in time, so the move from the search is played instead
+        let piece_move = weaker_move(2000, 2, &mut Random::new(1), &mut cycle_counter, &0, &time_limits, &[[0; 64]; 12], &EvalNoise::NONE, &mut TranspositionTable::<1024>::new(), &mut MoveHistory::new(), &board, &pieces_info);
+        assert!(piece_move.is_none());
+    }
+}
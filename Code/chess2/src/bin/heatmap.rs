@@ -0,0 +1,174 @@
+// Generates the opening heatmap used for move ordering from a PGN file of games
+// Counts how often each piece moves to each square in the first half moves of the games, and writes the table as a Rust source file
+// cargo run --release --bin heatmap --no-default-features --features host --target x86_64-unknown-linux-gnu -- games.pgn --plies 20 --max 2000 --output src/opening_heatmap.rs
+
+use std::fmt::Write;
+
+use chess2::board::board_representation::{Board, STARTING_FEN};
+use chess2::board::move_generator;
+use chess2::notation;
+use chess2::pgn::{Token, Tokens};
+
+struct Options {
+    input: String, // PGN file to read
+    output: Option<String>, // Rust file to write, the table is printed if there is no output file
+    plies: i16, // Number of half moves counted from the start of each game
+    max: Option<i16>, // The table is scaled so the largest value is max, otherwise the counts are used
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut args = std::env::args().skip(1);
+
+    let mut options = Options {
+        input: String::new(),
+        output: None,
+        plies: 20,
+        max: None,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => options.output = Some(args.next().ok_or("--output needs a file")?),
+            "--plies" => options.plies = args.next().and_then(|plies| plies.parse().ok()).filter(|plies| *plies > 0).ok_or("--plies needs a positive number")?,
+            "--max" => options.max = Some(args.next().and_then(|max| max.parse().ok()).filter(|max| *max > 0).ok_or("--max needs a positive number")?),
+            _ => options.input = arg,
+        }
+    }
+
+    if options.input.is_empty() {
+        return Err(String::from("Usage: heatmap <games.pgn> [--plies <half moves>] [--max <largest value>] [--output <file.rs>]"));
+    }
+    Ok(options)
+}
+
+fn main() {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(1);
+        },
+    };
+
+    // PGN files are often Latin-1, only the tags can contain non ascii characters
+    let bytes = std::fs::read(&options.input).unwrap_or_else(|error| {
+        eprintln!("Couldn't read {}: {}", options.input, error);
+        std::process::exit(1);
+    });
+    let text = String::from_utf8_lossy(&bytes);
+
+    let pieces_info = chess2::piece::constants::gen();
+
+    let mut counts = [[0u64; 64]; 12];
+    let mut games = 0;
+    let mut skipped_games = 0;
+
+    let mut board = Board::from_fen(STARTING_FEN).unwrap();
+    let mut game_started = false; // True once the first move of a game has been read
+    let mut game_valid = true; // False once a move of the game couldn't be replayed
+    let mut game_over = false; // True once a move ended the game (checkmate or stalemate), the board isn't updated after it
+    for token in Tokens::new(&text) {
+        match token {
+            Token::Tag(name, value) => {
+                // Games that don't start from the starting position are replayed from their fen tag
+                if name == "FEN" {
+                    match Board::from_fen(value) {
+                        Ok(fen_board) => board = fen_board,
+                        Err(_) => game_valid = false,
+                    }
+                }
+            },
+            Token::Move(san) => {
+                game_started = true;
+                if !game_valid || game_over || board.half_moves >= options.plies {
+                    continue;
+                }
+
+                let piece_move = match notation::parse_san(san, &board, &pieces_info) {
+                    Ok(piece_move) => piece_move,
+                    Err(error) => {
+                        eprintln!("Game {}: couldn't read move {} ({:?})", games + skipped_games + 1, san, error);
+                        game_valid = false;
+                        continue;
+                    },
+                };
+
+                counts[piece_move.initial_piece_coordinates.board_index][piece_move.final_piece_bit] += 1;
+
+                let (friendly_king, enemy_king) = board.kings();
+                let team_bitboards = chess2::TeamBitboards::new(friendly_king.board_index, &board);
+                let enemy_attacks = move_generator::gen_enemy_attacks(&friendly_king, team_bitboards, &board, &pieces_info);
+
+                // The moves are legal, so an error is a move that ends the game
+                match move_generator::new_turn(&piece_move.initial_piece_coordinates, piece_move.final_piece_bit, piece_move.promotion, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, board, &pieces_info) {
+                    Ok(new_board) => board = new_board,
+                    Err(_) => game_over = true,
+                }
+            },
+            Token::Result(_) => {
+                if game_started {
+                    if game_valid {
+                        games += 1;
+                    } else {
+                        skipped_games += 1;
+                    }
+                }
+
+                board = Board::from_fen(STARTING_FEN).unwrap();
+                game_started = false;
+                game_valid = true;
+                game_over = false;
+            },
+        }
+    }
+
+    let heatmap = normalise(&counts, options.max);
+
+    let mut source = String::new();
+    writeln!(source, "// Opening heatmap generated by the heatmap tool from {} games in {}", games, options.input).unwrap();
+    writeln!(source, "// Number of times each piece (board index) moved to each square (bit) in the first {} half moves", options.plies).unwrap();
+    if let Some(max) = options.max {
+        writeln!(source, "// Scaled so the largest value is {}", max).unwrap();
+    }
+    writeln!(source, "pub const OPENING_HEATMAP: [[i16; 64]; 12] = [").unwrap();
+    for values in &heatmap {
+        let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        writeln!(source, "    [{}],", values.join(", ")).unwrap();
+    }
+    writeln!(source, "];").unwrap();
+
+    match &options.output {
+        Some(output) => {
+            std::fs::write(output, source).unwrap_or_else(|error| {
+                eprintln!("Couldn't write {}: {}", output, error);
+                std::process::exit(1);
+            });
+            eprintln!("Wrote {} ({} games, {} skipped)", output, games, skipped_games);
+        },
+        None => print!("{}", source),
+    }
+}
+
+// Scales the counts so the largest value is max
+// Without a max the counts are used, unless they don't fit in an i16
+fn normalise(counts: &[[u64; 64]; 12], max: Option<i16>) -> [[i16; 64]; 12] {
+    let mut largest_count = 1;
+    for piece_counts in counts {
+        for &count in piece_counts {
+            largest_count = largest_count.max(count);
+        }
+    }
+
+    let max_value = match max {
+        Some(max) => max as u64,
+        None => largest_count.min(i16::MAX as u64),
+    };
+
+    let mut heatmap = [[0i16; 64]; 12];
+    for board_index in 0..12 {
+        for bit in 0..64 {
+            heatmap[board_index][bit] = (counts[board_index][bit] * max_value / largest_count) as i16;
+        }
+    }
+    heatmap
+}
@@ -0,0 +1,338 @@
+// UCI front end for the engine, so it can play other engines and be used in chess GUIs (e.g. Cute Chess)
+// Commands are read from stdin and responses are written to stdout
+// cargo build --release --bin uci --no-default-features --features host --target x86_64-unknown-linux-gnu
+
+use std::collections::VecDeque;
+use std::io::BufRead;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Instant;
+
+use chess2::algorithm::{self, AlphaBeta, CycleCounter, Move, MoveHistory, SearchInfo};
+use chess2::board::board_representation::{Board, STARTING_FEN};
+use chess2::board::move_generator;
+use chess2::board::move_generator::TurnError;
+use chess2::strength::EvalNoise;
+use chess2::time_management::TimeLimits;
+use chess2::transposition::TranspositionTable;
+
+// The same opening heatmap as the firmware, so the engine plays the same moves as on the chess board
+#[path = "../opening_heatmap.rs"]
+mod opening_heatmap;
+
+const TRANSPOSITION_ENTRIES: usize = 1 << 16;
+
+const MAX_DEPTH: usize = 30;
+
+// Like the firmware the opening heatmap is only used for the first half moves of the game
+const OPENING_HALF_MOVES: i16 = 20;
+
+// Time kept on the clock to cover the time the GUI takes to receive the move
+const CLOCK_SAFETY_US: u64 = 50000;
+
+// Hard limit for searches without a time limit, small enough that adding it to the start time doesn't overflow
+const NO_TIME_LIMIT: u64 = u64::MAX / 2;
+
+// Host clock for the search, counting microseconds from the start of the search
+// Once the stop flag is set the cycles jump past every time limit, so the search returns the best move of the deepest completed iteration
+struct SearchClock {
+    start: Instant,
+    micros: u64, // Microseconds from the start of the search at the last update
+    stop: Arc<AtomicBool>, // Set by the input thread when a stop or quit command is read
+    stopped: bool,
+}
+
+impl CycleCounter for SearchClock {
+    fn update(&mut self) {
+        self.micros = self.start.elapsed().as_micros() as u64;
+        if self.stop.load(Ordering::Relaxed) {
+            self.stopped = true;
+        }
+    }
+
+    fn cycles(&self) -> u64 {
+        if self.stopped {
+            u64::MAX
+        } else {
+            self.micros
+        }
+    }
+
+    fn iteration_complete(&mut self, search_info: &SearchInfo) {
+        print_info(search_info, self.start.elapsed().as_millis() as u64);
+    }
+}
+
+// Limits of a go command, times are in milliseconds
+struct GoOptions {
+    depth: usize,
+    movetime: Option<u64>,
+    time: [Option<u64>; 2], // Time left on the clock of the white and black team
+    increment: [u64; 2],
+    infinite: bool, // The best move is only sent once a stop command is read
+}
+
+fn main() {
+    let pieces_info = chess2::piece::constants::gen();
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let commands = read_commands(stop.clone());
+
+    let mut board = Board::from_fen(STARTING_FEN).unwrap();
+    let mut game_over = false; // True if the last move of the position ended the game, so there is no move to search
+    let mut transposition_table = Box::new(TranspositionTable::<TRANSPOSITION_ENTRIES>::new());
+    let mut move_history = MoveHistory::new();
+
+    // Commands read while waiting for the stop command of an infinite search, they are handled after the search
+    let mut pending = VecDeque::new();
+
+    loop {
+        let line = match pending.pop_front() {
+            Some(pending_line) => pending_line,
+            None => match commands.recv() {
+                Ok(command_line) => command_line,
+                Err(_) => break,
+            },
+        };
+        let mut words = line.split_ascii_whitespace();
+
+        match words.next() {
+            Some("uci") => {
+                println!("id name Chess2");
+                println!("id author Chess2 developers");
+                println!("uciok");
+            },
+            Some("ucinewgame") => {
+                transposition_table.clear();
+                move_history.clear();
+            },
+            Some("position") => {
+                match parse_position(words, &pieces_info) {
+                    Ok((position_board, position_game_over)) => {
+                        board = position_board;
+                        game_over = position_game_over;
+                    },
+                    Err(message) => println!("info string {}", message),
+                }
+            },
+            Some("go") => {
+                let options = parse_go(words);
+
+                // A move time takes priority over the clock, searches without either only stop at the depth limit or a stop command
+                let team_index = if board.whites_move { 0 } else { 1 };
+                let time_limits = if let Some(movetime) = options.movetime {
+                    TimeLimits::fixed(movetime * 1000)
+                } else if let Some(time) = options.time[team_index] {
+                    TimeLimits::from_clock(time * 1000, options.increment[team_index] * 1000, CLOCK_SAFETY_US, board.half_moves)
+                } else {
+                    TimeLimits::fixed(NO_TIME_LIMIT)
+                };
+
+                let best_move = if game_over {
+                    None
+                } else {
+                    search(&board, &time_limits, options.depth, &stop, &mut transposition_table, &mut move_history, &pieces_info)
+                };
+
+                // Infinite searches wait for the stop command even if the search has finished
+                if options.infinite && !wait_for_stop(&commands, &stop, &mut pending) {
+                    break;
+                }
+
+                match best_move {
+                    Some(piece_move) => println!("bestmove {}", piece_move.to_uci().as_str()),
+                    None => println!("bestmove 0000"),
+                }
+            },
+            // The stop command has been handled by the search, so the next search can start
+            Some("stop") => stop.store(false, Ordering::Relaxed),
+            Some("quit") => break,
+            _ => (),
+        }
+    }
+}
+
+// Reads commands on another thread, so stop and quit commands are seen while the engine is searching
+// The stop flag is set before the command is sent, and cleared once the main thread reads the command
+// isready is answered straight away, so the GUI gets an answer while the engine is searching
+fn read_commands(stop: Arc<AtomicBool>) -> mpsc::Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+
+            let command = line.trim();
+            if command == "isready" {
+                println!("readyok");
+                continue;
+            }
+            if command == "stop" || command == "quit" {
+                stop.store(true, Ordering::Relaxed);
+            }
+            if sender.send(line).is_err() {
+                return;
+            }
+        }
+
+        // The GUI closing stdin is handled like quit
+        stop.store(true, Ordering::Relaxed);
+        sender.send(String::from("quit")).ok();
+    });
+
+    receiver
+}
+
+// Waits until the stop command is read, other commands are added to pending so they are handled after the search
+// Returns false if the quit command is read instead
+fn wait_for_stop(commands: &mpsc::Receiver<String>, stop: &AtomicBool, pending: &mut VecDeque<String>) -> bool {
+    while let Ok(line) = commands.recv() {
+        match line.trim() {
+            "stop" => {
+                stop.store(false, Ordering::Relaxed);
+                return true;
+            },
+            "quit" => return false,
+            _ => pending.push_back(line),
+        }
+    }
+    false
+}
+
+// Reads the board from the arguments of a position command
+// E.g. "startpos moves e2e4 e7e5" or "fen 4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 moves e2e4"
+// Also returns true if the last move ended the game (checkmate or stalemate), the board before that move is returned since the board isn't updated
+fn parse_position<'a>(mut words: impl Iterator<Item = &'a str>, pieces_info: &[chess2::piece::constants::PieceInfo; 12]) -> Result<(Board, bool), String> {
+    let mut board;
+    match words.next() {
+        Some("startpos") => {
+            board = Board::from_fen(STARTING_FEN).unwrap();
+            match words.next() {
+                Some("moves") | None => (),
+                Some(word) => return Err(format!("Expected moves after startpos, not {}", word)),
+            }
+        },
+        Some("fen") => {
+            let fen: Vec<&str> = words.by_ref().take_while(|word| *word != "moves").collect();
+            let fen = fen.join(" ");
+            board = Board::from_fen(&fen).map_err(|error| format!("Invalid fen {} ({:?})", fen, error))?;
+        },
+        _ => return Err(String::from("Position needs startpos or fen")),
+    }
+
+    // TODO: Keep the positions of the moves, so threefold repetition is seen by the search and ends the game
+    let mut game_over = false;
+    for uci in words {
+        if game_over {
+            return Err(format!("Move {} is after the end of the game", uci));
+        }
+
+        let piece_move = Move::from_uci(uci, &board, pieces_info).map_err(|error| format!("Invalid move {} ({:?})", uci, error))?;
+
+        let (friendly_king, enemy_king) = board.kings();
+        let team_bitboards = chess2::TeamBitboards::new(friendly_king.board_index, &board);
+        let enemy_attacks = move_generator::gen_enemy_attacks(&friendly_king, team_bitboards, &board, pieces_info);
+
+        match move_generator::new_turn(&piece_move.initial_piece_coordinates, piece_move.final_piece_bit, piece_move.promotion, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, board, pieces_info) {
+            Ok(new_board) => board = new_board,
+            Err(TurnError::Win) | Err(TurnError::Draw) => game_over = true,
+            Err(error) => return Err(format!("Invalid move {} ({:?})", uci, error)),
+        }
+    }
+
+    Ok((board, game_over))
+}
+
+// Reads the limits of a go command, unknown options (e.g. movestogo) are ignored
+fn parse_go<'a>(mut words: impl Iterator<Item = &'a str>) -> GoOptions {
+    let mut options = GoOptions {
+        depth: MAX_DEPTH,
+        movetime: None,
+        time: [None; 2],
+        increment: [0; 2],
+        infinite: false,
+    };
+
+    while let Some(word) = words.next() {
+        let mut value = || words.next().and_then(|value| value.parse::<i64>().ok()).map(|value| value.max(0) as u64);
+        match word {
+            "depth" => options.depth = value().map_or(MAX_DEPTH, |depth| (depth as usize).clamp(1, MAX_DEPTH)),
+            "movetime" => options.movetime = value(),
+            "wtime" => options.time[0] = value(),
+            "btime" => options.time[1] = value(),
+            "winc" => options.increment[0] = value().unwrap_or(0),
+            "binc" => options.increment[1] = value().unwrap_or(0),
+            "infinite" => options.infinite = true,
+            _ => (),
+        }
+    }
+
+    options
+}
+
+// Searches the board until the time limit, depth or a stop command
+// Returns None if there are no legal moves
+fn search(
+    board: &Board,
+    time_limits: &TimeLimits,
+    search_depth: usize,
+    stop: &Arc<AtomicBool>,
+    transposition_table: &mut TranspositionTable<TRANSPOSITION_ENTRIES>,
+    move_history: &mut MoveHistory,
+    pieces_info: &[chess2::piece::constants::PieceInfo; 12]
+) -> Option<Move> {
+    let opening_heatmap = if board.half_moves > OPENING_HALF_MOVES {
+        [[0i16; 64]; 12]
+    } else {
+        opening_heatmap::OPENING_HEATMAP
+    };
+
+    let mut clock = SearchClock {
+        start: Instant::now(),
+        micros: 0,
+        stop: stop.clone(),
+        stopped: false,
+    };
+    let mut search_info = SearchInfo::new();
+    move_history.new_search();
+
+    // The first iteration of the search always finishes, so there is a move unless there are no legal moves
+    algorithm::gen_best_move(
+        true,
+        &mut clock,
+        &0,
+        time_limits,
+        search_depth,
+        0,
+        AlphaBeta::new(),
+        &opening_heatmap,
+        &EvalNoise::NONE,
+        transposition_table,
+        move_history,
+        &mut search_info,
+        *board,
+        pieces_info,
+    ).piece_move
+}
+
+// Prints the results of a completed iteration
+// Mate scores are sent as full moves until mate, negative if the engine gets mated
+fn print_info(search_info: &SearchInfo, elapsed_ms: u64) {
+    let score = if algorithm::is_mate_score(search_info.score) {
+        format!("mate {}", algorithm::mate_in(search_info.score))
+    } else {
+        format!("cp {}", search_info.score)
+    };
+
+    let mut pv = String::new();
+    for piece_move in search_info.pv() {
+        pv += " ";
+        pv += piece_move.to_uci().as_str();
+    }
+
+    let nodes = search_info.nodes as u64;
+    println!("info depth {} score {} nodes {} nps {} time {} pv{}", search_info.depth, score, nodes, nodes * 1000 / elapsed_ms.max(1), elapsed_ms, pv);
+}
@@ -1,7 +1,25 @@
+// Lints for the code style of the original move generator
+// E.g. late initialisation of variables with if/else blocks, and indexing arrays with loop variables
+#![allow(
+    clippy::assign_op_pattern,
+    clippy::collapsible_if,
+    clippy::collapsible_match,
+    clippy::manual_range_contains,
+    clippy::needless_bool_assign,
+    clippy::needless_late_init,
+    clippy::needless_range_loop,
+    clippy::new_without_default,
+    clippy::redundant_field_names,
+    clippy::single_match,
+    clippy::while_let_loop,
+)]
+
 use core::convert::TryFrom;
 use core::convert::TryInto;
 
 pub mod board_representation {
+    use core::fmt::Write;
+    use arrform::ArrForm;
 
     #[derive(Copy, Clone, PartialEq, Debug)]
     pub struct Points { 
@@ -46,6 +64,7 @@ pub mod board_representation {
         pub half_moves: i16, // The total number of half moves
         pub half_move_clock: i16, // The number of half moves since the last capture or pawn move
         pub en_passant_target: Option<usize>, // En passant target bit
+        pub key: u64, // Zobrist key of the position, updated by new_turn
     }
 
     // Coordinates used to reference a single piece on the board
@@ -79,6 +98,7 @@ pub mod board_representation {
                 half_moves: 0,
                 half_move_clock: 0,
                 en_passant_target: None,
+                key: crate::zobrist::WHITE_TO_MOVE_KEY, // Key of an empty board with white to move
             }
         }
 
@@ -93,6 +113,348 @@ pub mod board_representation {
 
             bitboard
         }
+
+        // Returns the friendly and enemy kings, relative to the team to move
+        pub fn kings(&self) -> (BoardCoordinates, BoardCoordinates) {
+            let friendly_king_index;
+            let enemy_king_index;
+            if self.whites_move {
+                friendly_king_index = 5;
+                enemy_king_index = 11;
+            } else {
+                friendly_king_index = 11;
+                enemy_king_index = 5;
+            }
+
+            let friendly_king = BoardCoordinates {
+                board_index: friendly_king_index,
+                bit: crate::find_bit_on(self.board[friendly_king_index], 0),
+            };
+
+            let enemy_king = BoardCoordinates {
+                board_index: enemy_king_index,
+                bit: crate::find_bit_on(self.board[enemy_king_index], 0),
+            };
+
+            (friendly_king, enemy_king)
+        }
+
+        // Creates a board from a fen string
+        // The halfmove clock and fullmove number fields are optional
+        pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+            let mut board = Board::new();
+            let mut fields = fen.split_ascii_whitespace();
+
+            // Piece placement
+            let placement = fields.next().ok_or(FenError::MissingField)?;
+            let mut rank = 0;
+            for rank_string in placement.split('/') {
+                if rank > 7 {
+                    return Err(FenError::InvalidRankCount);
+                }
+
+                let mut file = 0;
+                for c in rank_string.chars() {
+                    if file > 7 {
+                        return Err(FenError::InvalidRankLength(rank));
+                    }
+
+                    match crate::char_to_num(c, 0) {
+                        Ok(empty_squares) => {
+                            if empty_squares < 1 || empty_squares > 8 {
+                                return Err(FenError::InvalidRankLength(rank));
+                            }
+                            file += empty_squares as usize;
+                        },
+                        Err(()) => {
+                            let board_index = match PIECE_CHARS.iter().position(|&piece_char| piece_char == c) {
+                                Some(index) => index,
+                                None => return Err(FenError::InvalidPiece(c)),
+                            };
+
+                            // Pawns can't be on the first or last rank
+                            if (board_index == 0 || board_index == 6) && (rank == 0 || rank == 7) {
+                                return Err(FenError::PawnOnBackRank);
+                            }
+
+                            board.board[board_index] |= 1 << (rank * 8 + file);
+                            file += 1;
+                        },
+                    }
+                }
+
+                if file != 8 {
+                    return Err(FenError::InvalidRankLength(rank));
+                }
+                rank += 1;
+            }
+
+            if rank != 8 {
+                return Err(FenError::InvalidRankCount);
+            }
+
+            // Each team needs exactly one king
+            if crate::bits_on(board.board[5]) != 1 || crate::bits_on(board.board[11]) != 1 {
+                return Err(FenError::InvalidKingCount);
+            }
+
+            // Side to move
+            match fields.next() {
+                Some("w") => board.whites_move = true,
+                Some("b") => board.whites_move = false,
+                Some(_) => return Err(FenError::InvalidSideToMove),
+                None => return Err(FenError::MissingField),
+            }
+
+            // Every piece is treated as moved apart from pawns on their starting rank
+            // Kings and rooks are then marked as unmoved according to the castling rights
+            board.board[12] = !((board.board[0] & 0x00FF000000000000) | (board.board[6] & 0x000000000000FF00));
+
+            // Castling rights
+            let castling = fields.next().ok_or(FenError::MissingField)?;
+            if castling != "-" {
+                for c in castling.chars() {
+                    let (king_index, king_bit, rook_bit) = match c {
+                        'K' => (5, 60, 63),
+                        'Q' => (5, 60, 56),
+                        'k' => (11, 4, 7),
+                        'q' => (11, 4, 0),
+                        _ => return Err(FenError::InvalidCastling(c)),
+                    };
+
+                    // Each castling right can only be given once
+                    if !crate::bit_on(board.board[12], rook_bit) {
+                        return Err(FenError::InvalidCastling(c));
+                    }
+
+                    // The king and rook have to be on their starting squares to castle
+                    if !crate::bit_on(board.board[king_index], king_bit) || !crate::bit_on(board.board[king_index - 4], rook_bit) {
+                        return Err(FenError::CastlingWithoutPieces(c));
+                    }
+
+                    board.board[12] &= !(1 << king_bit | 1 << rook_bit);
+                }
+            }
+
+            // En passant target
+            let en_passant = fields.next().ok_or(FenError::MissingField)?;
+            if en_passant != "-" {
+                let bit = crate::ccn_to_bit(en_passant).ok_or(FenError::InvalidEnPassant)?;
+
+                // The target has to be behind a pawn which has just moved 2 squares
+                let valid_target;
+                if board.whites_move {
+                    valid_target = bit / 8 == 2 && crate::bit_on(board.board[6], bit + 8);
+                } else {
+                    valid_target = bit / 8 == 5 && crate::bit_on(board.board[0], bit - 8);
+                }
+
+                if !valid_target {
+                    return Err(FenError::InvalidEnPassant);
+                }
+                board.en_passant_target = Some(bit);
+            }
+
+            // Halfmove clock
+            if let Some(half_move_clock) = fields.next() {
+                board.half_move_clock = match half_move_clock.parse::<i16>() {
+                    Ok(clock) if clock >= 0 => clock,
+                    _ => return Err(FenError::InvalidHalfMoveClock),
+                };
+            }
+
+            // Fullmove number
+            if let Some(full_moves) = fields.next() {
+                let full_moves = match full_moves.parse::<i16>() {
+                    Ok(moves) if moves >= 1 && moves <= i16::MAX / 2 => moves,
+                    _ => return Err(FenError::InvalidFullMoveNumber),
+                };
+
+                board.half_moves = (full_moves - 1) * 2;
+            }
+
+            if !board.whites_move {
+                board.half_moves += 1;
+            }
+
+            if fields.next().is_some() {
+                return Err(FenError::TooManyFields);
+            }
+
+            board.key = board.hash();
+            Ok(board)
+        }
+
+        // Converts the board to a fen string
+        pub fn to_fen(&self) -> ArrForm<FEN_MAX_LEN> {
+            let mut fen = ArrForm::<FEN_MAX_LEN>::new();
+
+            // The buffer is large enough for any board, so writes can't fail
+            // Piece placement
+            for rank in 0..8 {
+                let mut empty_squares = 0;
+                for file in 0..8 {
+                    match crate::find_board_index(self, rank * 8 + file) {
+                        Ok(board_index) => {
+                            if empty_squares > 0 {
+                                write!(fen, "{}", empty_squares).ok();
+                                empty_squares = 0;
+                            }
+                            fen.write_char(PIECE_CHARS[board_index]).ok();
+                        },
+                        Err(()) => empty_squares += 1,
+                    }
+                }
+
+                if empty_squares > 0 {
+                    write!(fen, "{}", empty_squares).ok();
+                }
+
+                if rank < 7 {
+                    fen.write_char('/').ok();
+                }
+            }
+
+            // Side to move
+            if self.whites_move {
+                fen.write_str(" w ").ok();
+            } else {
+                fen.write_str(" b ").ok();
+            }
+
+            // Castling rights
+            let castling = self.castling_rights();
+            let castling_chars = ['K', 'Q', 'k', 'q'];
+            let mut can_castle = false;
+            for i in 0..castling.len() {
+                if castling[i] {
+                    fen.write_char(castling_chars[i]).ok();
+                    can_castle = true;
+                }
+            }
+
+            if !can_castle {
+                fen.write_char('-').ok();
+            }
+
+            // En passant target
+            match self.en_passant_target {
+                Some(bit) => write!(fen, " {}", crate::bit_to_ccn(bit)).ok(),
+                None => fen.write_str(" -").ok(),
+            };
+
+            // Halfmove clock and fullmove number
+            write!(fen, " {} {}", self.half_move_clock, self.half_moves / 2 + 1).ok();
+
+            fen
+        }
+
+        // Returns castling rights as [white king side, white queen side, black king side, black queen side]
+        // A team can castle on a side if the king and rook are on their starting squares and haven't moved
+        pub fn castling_rights(&self) -> [bool; 4] {
+            use crate::bit_on;
+
+            let mut castling_rights = [false; 4];
+            let squares: [(usize, usize, usize); 4] = [(5, 60, 63), (5, 60, 56), (11, 4, 7), (11, 4, 0)];
+
+            for i in 0..squares.len() {
+                let (king_index, king_bit, rook_bit) = squares[i];
+
+                castling_rights[i] = bit_on(self.board[king_index], king_bit)
+                    && bit_on(self.board[king_index - 4], rook_bit)
+                    && !bit_on(self.board[12], king_bit)
+                    && !bit_on(self.board[12], rook_bit);
+            }
+
+            castling_rights
+        }
+
+        // Calculates the zobrist key of the position from scratch
+        // Boards with the same pieces, team to move, castling rights, and capturable en passant target have the same key
+        pub fn hash(&self) -> u64 {
+            use crate::bit_on;
+            use crate::zobrist::PIECE_KEYS;
+
+            let mut key = self.state_key();
+            for i in 0..12 {
+                for bit in 0..64 {
+                    if bit_on(self.board[i], bit) {
+                        key ^= PIECE_KEYS[i][bit];
+                    }
+                }
+            }
+            key
+        }
+
+        // Returns true if the king of the team to move is attacked
+        pub fn in_check(&self) -> bool {
+            let (friendly_king, _) = self.kings();
+            super::move_generator::is_square_attacked(friendly_king.bit, !self.whites_move, self)
+        }
+
+        // Part of the zobrist key that doesn't come from piece positions (team to move, castling rights, and en passant file)
+        pub fn state_key(&self) -> u64 {
+            use crate::bit_on;
+            use crate::zobrist::{CASTLING_KEYS, EN_PASSANT_KEYS, WHITE_TO_MOVE_KEY};
+
+            let mut key = 0;
+            if self.whites_move {
+                key ^= WHITE_TO_MOVE_KEY;
+            }
+
+            let castling_rights = self.castling_rights();
+            for i in 0..castling_rights.len() {
+                if castling_rights[i] {
+                    key ^= CASTLING_KEYS[i];
+                }
+            }
+
+            // The en passant target only changes the position if a pawn can capture on it
+            if let Some(en_passant_target) = self.en_passant_target {
+                let pawn_bit; // Bit of the pawn that can be captured
+                let capturing_index; // Board index of the pawns that can capture
+                if self.whites_move {
+                    pawn_bit = en_passant_target + 8;
+                    capturing_index = 0;
+                } else {
+                    pawn_bit = en_passant_target - 8;
+                    capturing_index = 6;
+                }
+
+                let file = en_passant_target % 8;
+                if (file > 0 && bit_on(self.board[capturing_index], pawn_bit - 1)) || (file < 7 && bit_on(self.board[capturing_index], pawn_bit + 1)) {
+                    key ^= EN_PASSANT_KEYS[file];
+                }
+            }
+            key
+        }
+    }
+
+    // Fen string for the standard starting position
+    pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    // Maximum length of a fen string created by to_fen
+    pub const FEN_MAX_LEN: usize = 96;
+
+    // Fen characters for each piece, in board index order
+    pub const PIECE_CHARS: [char; 12] = ['P', 'R', 'N', 'B', 'Q', 'K', 'p', 'r', 'n', 'b', 'q', 'k'];
+
+    // Errors that can be encountered when parsing a fen string
+    #[derive(PartialEq, Debug)]
+    pub enum FenError {
+        MissingField, // The fen string doesn't have the placement, side to move, castling, and en passant fields
+        TooManyFields, // The fen string has more than 6 fields
+        InvalidRankCount, // The piece placement doesn't have 8 ranks
+        InvalidRankLength(usize), // A rank doesn't describe exactly 8 squares (rank 0 is the top of the board)
+        InvalidPiece(char), // The piece placement contains an unknown piece
+        PawnOnBackRank, // A pawn is on the first or last rank
+        InvalidKingCount, // A team doesn't have exactly one king
+        InvalidSideToMove, // The side to move isn't 'w' or 'b'
+        InvalidCastling(char), // The castling field contains an unknown or repeated character
+        CastlingWithoutPieces(char), // A castling right was given without the king and rook on their starting squares
+        InvalidEnPassant, // The en passant target isn't a square behind a pawn that has just moved 2 squares
+        InvalidHalfMoveClock, // The halfmove clock isn't a non-negative number
+        InvalidFullMoveNumber, // The fullmove number isn't a number greater than 0
     }
 }
 
@@ -142,7 +504,7 @@ pub mod move_generator {
 
         // Initialize moves with pawn capture moves if the piece is a pawn
         if piece_pawn {
-            moves = gen_pawn_captures(&piece, only_gen_attacks, *team_bitboards, &board);
+            moves = gen_pawn_captures(piece, only_gen_attacks, *team_bitboards, board);
         }
 
         if only_gen_attacks && piece_pawn // Do not generate regular moves for pawns if only_gen_attacks is true
@@ -369,6 +731,235 @@ pub mod move_generator {
         Moves::new()
     }
 
+    // Most legal moves any chess position can have, the size of the lists returned by legal_moves
+    pub const MAX_MOVES: usize = 218;
+
+    // Number of semi legal moves the search generates for each position
+    // Kept small so a move list for every search depth fits in the STM32's ram, moves past this aren't searched
+    pub const MAX_SEARCH_MOVES: usize = 96;
+
+    // Fixed size list of moves, only the first len moves are valid
+    #[derive(Copy, Clone, Debug)]
+    pub struct MoveList<const N: usize = MAX_MOVES> {
+        pub moves: [crate::algorithm::Move; N],
+        pub len: usize,
+    }
+
+    impl<const N: usize> MoveList<N> {
+        pub fn new() -> Self {
+            MoveList {
+                moves: [crate::algorithm::Move::new(); N],
+                len: 0,
+            }
+        }
+
+        // Adds a move to the end of the list
+        // Moves past N are dropped rather than panicking on the board
+        pub fn push(&mut self, piece_move: crate::algorithm::Move) {
+            if self.len < N {
+                self.moves[self.len] = piece_move;
+                self.len += 1;
+            }
+        }
+
+        // Returns the valid moves in the list
+        pub fn as_slice(&self) -> &[crate::algorithm::Move] {
+            &self.moves[..self.len]
+        }
+
+        // Returns the valid moves in the list
+        pub fn as_mut_slice(&mut self) -> &mut [crate::algorithm::Move] {
+            &mut self.moves[..self.len]
+        }
+    }
+
+    // Board indexes (relative to the teams pawn index) of the pieces a pawn can promote to
+    // Queen, rook, knight, bishop
+    pub const PROMOTION_INDEXES: [usize; 4] = [4, 1, 2, 3];
+
+    // Generates semi legal moves for the team to move, including castling moves
+    // The moves can still leave the king in check, new_turn decides if a move is legal
+    pub fn gen_moves<const N: usize>(
+        friendly_king: &board_representation::BoardCoordinates,
+        enemy_attacks: &EnemyAttacks,
+        team_bitboards: &crate::TeamBitboards,
+        board: &board_representation::Board,
+        pieces_info: &[crate::piece::constants::PieceInfo; 12]
+    ) -> MoveList<N> {
+        let mut moves = MoveList::new();
+        for_each_move(friendly_king, enemy_attacks, team_bitboards, board, pieces_info, |piece_move| moves.push(piece_move));
+        moves
+    }
+
+    // Calls add_move for each semi legal move of the team to move, so the moves can be used without storing them in a list
+    pub fn for_each_move(
+        friendly_king: &board_representation::BoardCoordinates,
+        enemy_attacks: &EnemyAttacks,
+        team_bitboards: &crate::TeamBitboards,
+        board: &board_representation::Board,
+        pieces_info: &[crate::piece::constants::PieceInfo; 12],
+        mut add_move: impl FnMut(crate::algorithm::Move)
+    ) {
+        use crate::bit_on;
+        use crate::algorithm::Move;
+
+        // Get friendly board indexes
+        let friendly_indexes;
+        if board.whites_move {
+            friendly_indexes = 0..6;
+        } else {
+            friendly_indexes = 6..12;
+        }
+
+        for board_index in friendly_indexes {
+            for initial_bit in 0..64 {
+
+                // If there is no piece on the board at this bit go to the next bit
+                if !bit_on(board.board[board_index], initial_bit) {
+                    continue;
+                }
+
+                let initial_piece_coordinates = board_representation::BoardCoordinates {
+                    board_index: board_index,
+                    bit: initial_bit,
+                };
+
+                let piece_moves = gen_piece(&initial_piece_coordinates, None, team_bitboards, false, board, pieces_info);
+
+                for final_bit in 0..64 {
+                    if bit_on(piece_moves.moves_bitboard, final_bit) {
+                        let mut piece_move = Move::new();
+                        piece_move.initial_piece_coordinates = initial_piece_coordinates;
+                        piece_move.final_piece_bit = final_bit;
+
+                        // Add a move for every piece a pawn can promote to when it reaches the top row (for its respective team)
+                        if (board_index == 0 && final_bit < 8) || (board_index == 6 && final_bit > 55) {
+                            for promotion_index in PROMOTION_INDEXES {
+                                piece_move.promotion = Some(promotion_index + board_index);
+                                add_move(piece_move);
+                            }
+                        } else {
+                            add_move(piece_move);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Castling moves aren't a part of gen_piece, so they are added seperately
+        for king_move_bit in [friendly_king.bit.wrapping_sub(2), friendly_king.bit + 2] {
+            if king_move_bit > 63 {
+                continue;
+            }
+
+            let castle_moves = castle(friendly_king, king_move_bit, team_bitboards, enemy_attacks.enemy_attack_bitboard, board);
+            if bit_on(castle_moves.moves_bitboard, king_move_bit) {
+                let mut piece_move = Move::new();
+                piece_move.initial_piece_coordinates = *friendly_king;
+                piece_move.final_piece_bit = king_move_bit;
+                add_move(piece_move);
+            }
+        }
+    }
+
+    // Returns every legal move for the team to move
+    // Moves that end the game (checkmate or stalemate) are legal
+    pub fn legal_moves(board: &board_representation::Board, pieces_info: &[crate::piece::constants::PieceInfo; 12]) -> MoveList {
+        let (friendly_king, enemy_king) = board.kings();
+        let team_bitboards = crate::TeamBitboards::new(friendly_king.board_index, board);
+        let enemy_attacks = gen_enemy_attacks(&friendly_king, team_bitboards, board, pieces_info);
+
+        // Semi legal moves are checked as they are generated, so the list only needs space for the legal moves
+        let mut legal_moves = MoveList::new();
+        for_each_move(&friendly_king, &enemy_attacks, &team_bitboards, board, pieces_info, |piece_move| {
+            let new_turn_board = new_turn(&piece_move.initial_piece_coordinates, piece_move.final_piece_bit, piece_move.promotion, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, *board, pieces_info);
+
+            match new_turn_board {
+                Ok(_) | Err(TurnError::Win) | Err(TurnError::Draw) => legal_moves.push(piece_move),
+                Err(TurnError::InvalidMove) | Err(TurnError::InvalidMoveCheck) => (),
+            }
+        });
+
+        legal_moves
+    }
+
+    // Returns true if the move is legal for the team to move
+    // Like legal_moves, moves that end the game are legal, and a pawn moving to the last row is checked as a promotion to a queen if it has no promotion
+    pub fn is_legal(piece_move: &crate::algorithm::Move, board: &board_representation::Board, pieces_info: &[crate::piece::constants::PieceInfo; 12]) -> bool {
+        let (friendly_king, enemy_king) = board.kings();
+        let team_bitboards = crate::TeamBitboards::new(friendly_king.board_index, board);
+        let enemy_attacks = gen_enemy_attacks(&friendly_king, team_bitboards, board, pieces_info);
+
+        match new_turn(&piece_move.initial_piece_coordinates, piece_move.final_piece_bit, piece_move.promotion, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, *board, pieces_info) {
+            Ok(_) | Err(TurnError::Win) | Err(TurnError::Draw) => true,
+            Err(TurnError::InvalidMove) | Err(TurnError::InvalidMoveCheck) => false,
+        }
+    }
+
+    // Counts the number of leaf nodes in the legal move tree at the given depth
+    // Used to check the move generator against known node counts
+    pub fn perft(board: &board_representation::Board, depth: usize, pieces_info: &[crate::piece::constants::PieceInfo; 12]) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let (friendly_king, enemy_king) = board.kings();
+        let team_bitboards = crate::TeamBitboards::new(friendly_king.board_index, board);
+        let enemy_attacks = gen_enemy_attacks(&friendly_king, team_bitboards, board, pieces_info);
+
+        let mut nodes = 0;
+        for_each_move(&friendly_king, &enemy_attacks, &team_bitboards, board, pieces_info, |piece_move| {
+            let new_turn_board = new_turn(&piece_move.initial_piece_coordinates, piece_move.final_piece_bit, piece_move.promotion, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, *board, pieces_info);
+
+            match new_turn_board {
+                Ok(new_board) => nodes += perft(&new_board, depth - 1, pieces_info),
+
+                // Moves that end the game are leaf nodes, there are no moves after them
+                Err(TurnError::Win) | Err(TurnError::Draw) => {
+                    if depth == 1 {
+                        nodes += 1;
+                    }
+                },
+                Err(TurnError::InvalidMove) | Err(TurnError::InvalidMoveCheck) => (),
+            }
+        });
+
+        nodes
+    }
+
+    // Runs perft for every legal move at the root
+    // Returns the legal moves and the node count of each move, at the same index as the move
+    pub fn divide(board: &board_representation::Board, depth: usize, pieces_info: &[crate::piece::constants::PieceInfo; 12]) -> (MoveList, [u64; MAX_MOVES]) {
+        let moves = legal_moves(board, pieces_info);
+        let mut nodes = [0; MAX_MOVES];
+
+        if depth == 0 {
+            return (moves, nodes);
+        }
+
+        let (friendly_king, enemy_king) = board.kings();
+        let team_bitboards = crate::TeamBitboards::new(friendly_king.board_index, board);
+        let enemy_attacks = gen_enemy_attacks(&friendly_king, team_bitboards, board, pieces_info);
+
+        for i in 0..moves.len {
+            let piece_move = moves.moves[i];
+            let new_turn_board = new_turn(&piece_move.initial_piece_coordinates, piece_move.final_piece_bit, piece_move.promotion, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, *board, pieces_info);
+
+            nodes[i] = match new_turn_board {
+                Ok(new_board) => perft(&new_board, depth - 1, pieces_info),
+                Err(_) => {
+                    if depth == 1 {
+                        1
+                    } else {
+                        0
+                    }
+                },
+            };
+        }
+
+        (moves, nodes)
+    }
+
     #[derive(PartialEq, Debug)]
     pub struct EnemyAttacks {
         pub enemy_attack_bitboard: u64,
@@ -376,6 +967,80 @@ pub mod move_generator {
         checking_pieces_no: usize,
     }
 
+    // Returns true if a piece of the given team attacks the bit
+    // Looks outwards from the bit for attacking pieces, so it is much cheaper than generating all enemy attacks
+    pub fn is_square_attacked(bit: usize, by_white: bool, board: &board_representation::Board) -> bool {
+        use crate::{bit_on, bit_move_valid};
+
+        const KNIGHT_MOVES: [i8; 8] = [-17, -15, -6, 10, 17, 15, 6, -10];
+        const STRAIGHT_MOVES: [i8; 4] = [-8, 1, 8, -1];
+        const DIAGONAL_MOVES: [i8; 4] = [-9, -7, 9, 7];
+
+        // Board indexes of the attacking team, and the bit changes from the attacked bit to a pawn that attacks it
+        let first_index;
+        let pawn_moves: [i8; 2];
+        if by_white {
+            first_index = 0;
+            pawn_moves = [7, 9]; // White pawns attack towards lower bits
+        } else {
+            first_index = 6;
+            pawn_moves = [-7, -9];
+        }
+
+        let pawns = board.board[first_index];
+        let rooks_queens = board.board[first_index + 1] | board.board[first_index + 4];
+        let knights = board.board[first_index + 2];
+        let bishops_queens = board.board[first_index + 3] | board.board[first_index + 4];
+        let king = board.board[first_index + 5];
+
+        // Pieces that move a single step (pawns, knights, and the king)
+        for i in 0..2 {
+            if bit_move_valid(bit, pawn_moves[i]) && bit_on(pawns, (bit as i8 + pawn_moves[i]) as usize) {
+                return true;
+            }
+        }
+        for i in 0..8 {
+            if bit_move_valid(bit, KNIGHT_MOVES[i]) && bit_on(knights, (bit as i8 + KNIGHT_MOVES[i]) as usize) {
+                return true;
+            }
+        }
+        for i in 0..4 {
+            if bit_move_valid(bit, STRAIGHT_MOVES[i]) && bit_on(king, (bit as i8 + STRAIGHT_MOVES[i]) as usize) {
+                return true;
+            }
+            if bit_move_valid(bit, DIAGONAL_MOVES[i]) && bit_on(king, (bit as i8 + DIAGONAL_MOVES[i]) as usize) {
+                return true;
+            }
+        }
+
+        // Sliding pieces, the first piece in each direction blocks the pieces behind it
+        let pieces = board.to_bitboard();
+        for i in 0..8 {
+            let delta_bit;
+            let sliders;
+            if i < 4 {
+                delta_bit = STRAIGHT_MOVES[i];
+                sliders = rooks_queens;
+            } else {
+                delta_bit = DIAGONAL_MOVES[i - 4];
+                sliders = bishops_queens;
+            }
+
+            let mut current_bit = bit;
+            while bit_move_valid(current_bit, delta_bit) {
+                current_bit = (current_bit as i8 + delta_bit) as usize;
+                if bit_on(pieces, current_bit) {
+                    if bit_on(sliders, current_bit) {
+                        return true;
+                    }
+                    break;
+                }
+            }
+        }
+
+        false
+    }
+
     // Generates atacks of enemys to the kings team, stores enemy pieces that put the king in check
     pub fn gen_enemy_attacks(king: &board_representation::BoardCoordinates, team_bitboards: crate::TeamBitboards, board: &board_representation::Board, pieces_info: &[crate::piece::constants::PieceInfo; 12]) -> EnemyAttacks {
         use board_representation::BoardCoordinates;
@@ -543,7 +1208,7 @@ pub mod move_generator {
                                     board.board[board_index] ^= 1 << initial_bit | 1 << final_bit; // Move friendly piece on board
                                     
                                     // Regenerate enemy attacks after capturing the checking piece
-                                    let enemy_attacks = gen_enemy_attacks(&king, team_bitboards, &board, pieces_info);
+                                    let enemy_attacks = gen_enemy_attacks(king, team_bitboards, &board, pieces_info);
                         
                                     // If there is a new checking piece after capturing the original one then the king is in mate, otherwise the king is not in mate
                                     match enemy_attacks.checking_pieces[0] {
@@ -657,9 +1322,12 @@ pub mod move_generator {
 
     // Move piece to piece_move_bit if the move is valid
     // If move is valid update the board, else return an error
+    // Promotion is the board index of the piece a pawn promotes to, pawns promote to queens if it is None
+    #[allow(clippy::too_many_arguments)] // Everything the move generator knows about the board is passed in, so it isn't generated again
     pub fn new_turn(
         piece: &board_representation::BoardCoordinates,
         piece_move_bit: usize,
+        promotion: Option<usize>,
         mut friendly_king: board_representation::BoardCoordinates,
         enemy_king: &board_representation::BoardCoordinates,
         enemy_attacks: &EnemyAttacks,
@@ -669,6 +1337,7 @@ pub mod move_generator {
     ) -> Result<board_representation::Board, TurnError> {
         use crate::TeamBitboards;
         use crate::board_index_white;
+        use crate::zobrist::PIECE_KEYS;
 
         // If the piece is a king generate castle moves
         let mut piece_moves = Moves::new();
@@ -684,6 +1353,32 @@ pub mod move_generator {
             return Err(TurnError::InvalidMove);
         }
 
+        // Pawns promote when they reach the top row (for their respective team)
+        let promoting = (piece.board_index == 0 && piece_move_bit < 8) || (piece.board_index == 6 && piece_move_bit > 55);
+
+        // Get the board index of the piece the pawn promotes to
+        let promotion_index = match promotion {
+            Some(index) => {
+
+                // Pawns can only promote to a friendly rook, knight, bishop, or queen, and only when they reach the top row
+                if !promoting || index > 11 || board_index_white(index) != piece_white || index % 6 == 0 || index % 6 == 5 {
+                    return Err(TurnError::InvalidMove);
+                }
+                index
+            },
+            None => {
+                if piece_white {
+                    4
+                } else {
+                    10
+                }
+            },
+        };
+
+        // Remove the team to move, castling rights, and en passant file from the key
+        // They are added back once the move has been made
+        let mut key = board.key ^ board.state_key();
+
         // If the castle was valid move the rook
         // If the castle was not valid generate regular piece moves
         let mut castled = false;
@@ -701,6 +1396,7 @@ pub mod move_generator {
 
                 // Remove rook rook_remove_bit and add rook at rook_add_bit
                 board.board[friendly_rook_board_index] ^= 1 << rook_remove_bit | 1 << rook_add_bit;
+                key ^= PIECE_KEYS[friendly_rook_board_index][rook_remove_bit] ^ PIECE_KEYS[friendly_rook_board_index][rook_add_bit];
                 castled = true;
             },
             None => piece_moves = gen_piece(piece, None, &team_bitboards, false, &board, pieces_info), // Gen non castle moves
@@ -711,24 +1407,27 @@ pub mod move_generator {
             return Err(TurnError::InvalidMove);
         }
 
+        // The en passant capture bit is set whenever a pawn could capture en passant
+        // So only remove the captured pawn if the pawn actually moved to the en passant target
+        let en_passant_capture = !castled && piece_moves.en_passant_capture_bit.is_some() && board.en_passant_target == Some(piece_move_bit);
+
         if !castled {
 
             // Remove en passant capture from the board
-            match piece_moves.en_passant_capture_bit {
-                Some(capture_bit) => {
-                    let en_passant_capture_xor_bitboard: u64 = 1 << capture_bit;
-                        
-                        // Get enemy pawn board index
-                        let enemy_pawn_board_index;
-                        if piece_white {
-                            enemy_pawn_board_index = 6;
-                        } else {
-                            enemy_pawn_board_index = 0;
-                        }
-                        
-                        board.board[enemy_pawn_board_index] ^= en_passant_capture_xor_bitboard; // Remove en passant capture piece from the board
-                },
-                None => (),
+            if en_passant_capture {
+                let en_passant_capture_xor_bitboard: u64 = 1 << piece_moves.en_passant_capture_bit.unwrap();
+
+                // Get enemy pawn board index
+                let enemy_pawn_board_index;
+                if piece_white {
+                    enemy_pawn_board_index = 6;
+                } else {
+                    enemy_pawn_board_index = 0;
+                }
+
+                board.board[enemy_pawn_board_index] ^= en_passant_capture_xor_bitboard; // Remove en passant capture piece from the board
+                team_bitboards.enemy_team ^= en_passant_capture_xor_bitboard; // Remove en passant capture piece from the enemy team bitboard
+                key ^= PIECE_KEYS[enemy_pawn_board_index][piece_moves.en_passant_capture_bit.unwrap()];
             }
 
             // Update board en passant target
@@ -741,19 +1440,17 @@ pub mod move_generator {
         team_bitboards.friendly_team ^= piece_move_xor_bitboard;
         board.board[12] |= piece_move_bitboard;
 
-        // Promote pawns to queens if they are in the top row (for their respective team)
-        // Give value for promoting pawns to queens
+        // Promote pawns if they are in the top row (for their respective team)
+        // Give value for promoting pawns, the value of the new piece minus the value of the pawn
         let mut value = 0;
-        if piece_white && piece.board_index == 0 && piece_move_bit < 8 {
+        if promoting {
             board.board[piece.board_index] ^= 1 << piece.bit;
-            board.board[4] |= piece_move_bitboard;
-            value += 8;
-        } else if !piece_white && piece.board_index == 6 && piece_move_bit > 55 {
-            board.board[piece.board_index] ^= 1 << piece.bit;
-            board.board[10] |= piece_move_bitboard;
-            value += 8;
+            board.board[promotion_index] |= piece_move_bitboard;
+            value += pieces_info[promotion_index].value - pieces_info[piece.board_index].value;
+            key ^= PIECE_KEYS[piece.board_index][piece.bit] ^ PIECE_KEYS[promotion_index][piece_move_bit];
         } else {
             board.board[piece.board_index] ^= piece_move_xor_bitboard; // Else move piece on its bitboard to the new coordinates
+            key ^= PIECE_KEYS[piece.board_index][piece.bit] ^ PIECE_KEYS[piece.board_index][piece_move_bit];
         }
 
         // Update friendly king bit if it was moved
@@ -778,19 +1475,16 @@ pub mod move_generator {
                 let new_piece_bitboard = board.board[i] ^ piece_move_bitboard ;
                 if new_piece_bitboard < board.board[i] {
                     board.board[i] = new_piece_bitboard;
-                    value = pieces_info[i].value;
+                    value += pieces_info[i].value; // Add to the value so promotion value isn't lost when a pawn promotes with a capture
+                    key ^= PIECE_KEYS[i][piece_move_bit];
                     break;
                 }
             }
         }
 
         // If a piece was captured with en passant its value is 1
-        // Don't set value to 1 if piece castled, because en_passant_capture_bit is also set when castled = true
-        if !castled {
-            match piece_moves.en_passant_capture_bit {
-                Some(_) => value = 1,
-                None => (),
-            }
+        if en_passant_capture {
+            value = 1;
         }
         
         // Don't keep en passant target from the piece moves if the pawn didn't move 2 squares
@@ -828,6 +1522,7 @@ pub mod move_generator {
 
         // The team to move is now opposite
         board.whites_move = !board.whites_move;
+        board.key = key ^ board.state_key();
 
         let friendly_attacks = gen_enemy_attacks(enemy_king, enemy_team_bitboards, &board, pieces_info); // Get friendly attacks to use as enemy attacks for enemy king
         let enemy_mate =  is_mate(enemy_king, &friendly_attacks, enemy_team_bitboards, &board, pieces_info);
@@ -851,6 +1546,22 @@ pub mod move_generator {
         Ok(board)
     }
 
+    // Passes the turn to the other team without moving a piece, used by null move pruning
+    // The team passing the turn must not be in check
+    pub fn null_turn(board: &board_representation::Board) -> board_representation::Board {
+        let mut new_board = *board;
+        let key = board.key ^ board.state_key();
+
+        new_board.whites_move = !board.whites_move;
+        new_board.en_passant_target = None; // The en passant capture is lost by not taking it
+        new_board.half_moves += 1;
+        new_board.half_move_clock += 1;
+        new_board.points_delta = 0;
+        new_board.key = key ^ new_board.state_key();
+
+        new_board
+    }
+
     // Returns a bitboard where a piece is moved from inital by delta bit
     // Only moves if the piece will still be on the board
     fn move_piece(initial_bit: usize, delta_bit: i8) -> Result<u64, ()> {
@@ -870,3 +1581,586 @@ pub mod move_generator {
         Err(())
     }
 }
+
+// Keeps track of the game so draws that depend on more than the current board can be found
+pub mod game_state {
+    use core::fmt::Write;
+
+    use arrform::ArrForm;
+
+    use super::board_representation::{Board, STARTING_FEN};
+    use super::move_generator::{self, TurnError};
+    use crate::algorithm::Move;
+    use crate::pgn::GameResult;
+
+    // Positions are only repeatable since the last capture or pawn move, and the fifty move rule ends the game after 100 half moves
+    pub const MAX_HISTORY: usize = 101;
+
+    // Number of half moves recorded for the PGN of a game, later moves aren't recorded and the PGN notes they are missing
+    // Each move uses 2 bytes, so the moves take 600 bytes of the 20 KB of RAM (the whole GameState is about 1.7 KB)
+    pub const MAX_GAME_MOVES: usize = 300;
+
+    // Lines of PGN movetext are wrapped to this length
+    const PGN_LINE_LEN: usize = 80;
+
+    // Not Copy since it is large for the stack of the chess board, copies have to be explicit clones
+    #[derive(Clone, PartialEq, Debug)]
+    pub struct GameState {
+        pub board: Board,
+        history: [u64; MAX_HISTORY], // Keys of the positions since the last capture or pawn move
+        history_len: usize,
+        start_board: Board, // Board at the start of the game, the recorded moves are replayed from it
+        moves: [u16; MAX_GAME_MOVES], // Moves of the game in the 2 byte Polyglot format
+        moves_len: usize,
+        moves_dropped: bool, // True if moves were played after the recorded moves were full
+    }
+
+    impl GameState {
+        pub fn new(board: Board) -> Self {
+            let mut history = [0; MAX_HISTORY];
+            history[0] = board.key;
+
+            GameState {
+                board: board,
+                history: history,
+                history_len: 1,
+                start_board: board,
+                moves: [0; MAX_GAME_MOVES],
+                moves_len: 0,
+                moves_dropped: false,
+            }
+        }
+
+        // Makes a move on the board and records the new position
+        // Returns TurnError::Draw for threefold repetition and dead positions, in this case the board is still updated
+        // Other errors are the same as move_generator::new_turn, and leave the board unchanged
+        pub fn new_turn(&mut self, piece_move: &Move, pieces_info: &[crate::piece::constants::PieceInfo; 12]) -> Result<(), TurnError> {
+            let (friendly_king, enemy_king) = self.board.kings();
+            let team_bitboards = crate::TeamBitboards::new(friendly_king.board_index, &self.board);
+            let enemy_attacks = move_generator::gen_enemy_attacks(&friendly_king, team_bitboards, &self.board, pieces_info);
+
+            let new_turn_board = move_generator::new_turn(&piece_move.initial_piece_coordinates, piece_move.final_piece_bit, piece_move.promotion, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, self.board, pieces_info);
+
+            // Record legal moves, including moves that end the game where the board isn't updated
+            match new_turn_board {
+                Ok(_) | Err(TurnError::Win) | Err(TurnError::Draw) => {
+                    if self.moves_len < MAX_GAME_MOVES {
+                        self.moves[self.moves_len] = crate::book::encode_move(piece_move, &self.board);
+                        self.moves_len += 1;
+                    } else {
+                        self.moves_dropped = true;
+                    }
+                },
+                Err(_) => (),
+            }
+
+            self.board = new_turn_board?;
+
+            // Earlier positions can't be repeated after a capture or pawn move
+            if self.board.half_move_clock == 0 {
+                self.history_len = 0;
+            }
+
+            // Drop the oldest position if the history is full
+            if self.history_len == MAX_HISTORY {
+                self.history.rotate_left(1);
+                self.history_len -= 1;
+            }
+
+            self.history[self.history_len] = self.board.key;
+            self.history_len += 1;
+
+            if self.repetitions() >= 3 || insufficient_material(&self.board) {
+                return Err(TurnError::Draw);
+            }
+            Ok(())
+        }
+
+        // Number of recorded half moves
+        pub fn moves_len(&self) -> usize {
+            self.moves_len
+        }
+
+        // Writes the game in PGN, with the moves in standard algebraic notation
+        // Only the first MAX_GAME_MOVES half moves are written, a comment after them notes the later moves are missing
+        // player_white is the team of the player on the chess board, the engine played the other team
+        pub fn to_pgn(&self, result: GameResult, player_white: bool, pieces_info: &[crate::piece::constants::PieceInfo; 12], pgn: &mut impl Write) -> core::fmt::Result {
+            let white;
+            let black;
+            if player_white {
+                white = "Player";
+                black = "Chess2";
+            } else {
+                white = "Chess2";
+                black = "Player";
+            }
+
+            // Seven tag roster, the board doesn't know the date or place of the game
+            writeln!(pgn, "[Event \"Chess board game\"]")?;
+            writeln!(pgn, "[Site \"?\"]")?;
+            writeln!(pgn, "[Date \"????.??.??\"]")?;
+            writeln!(pgn, "[Round \"-\"]")?;
+            writeln!(pgn, "[White \"{}\"]", white)?;
+            writeln!(pgn, "[Black \"{}\"]", black)?;
+            writeln!(pgn, "[Result \"{}\"]", result.as_str())?;
+
+            // Games that don't start from the starting position need the starting board
+            if self.start_board.key != Board::from_fen(STARTING_FEN).unwrap().key {
+                writeln!(pgn, "[SetUp \"1\"]")?;
+                writeln!(pgn, "[FEN \"{}\"]", self.start_board.to_fen().as_str())?;
+            }
+            writeln!(pgn)?;
+
+            // Movetext, the moves are replayed from the starting board to write them in standard algebraic notation
+            let mut board = self.start_board;
+            let mut line_len = 0;
+            for i in 0..self.moves_len + 1 {
+                let mut text = ArrForm::<48>::new();
+                if i == self.moves_len {
+                    // The moves after the recorded moves are missing, so the result doesn't follow from the movetext
+                    if self.moves_dropped {
+                        text.write_str("{Later moves were not recorded} ")?;
+                    }
+                    text.write_str(result.as_str())?;
+                } else {
+                    let piece_move = match crate::book::decode_move(self.moves[i], &board, pieces_info) {
+                        Some(piece_move) => piece_move,
+                        None => break,
+                    };
+
+                    // White moves and the first move of the game are numbered
+                    if board.whites_move {
+                        write!(text, "{}. ", board.half_moves / 2 + 1)?;
+                    } else if i == 0 {
+                        write!(text, "{}... ", board.half_moves / 2 + 1)?;
+                    }
+                    text.write_str(crate::notation::to_san(&piece_move, &board, pieces_info).as_str())?;
+
+                    let (friendly_king, enemy_king) = board.kings();
+                    let team_bitboards = crate::TeamBitboards::new(friendly_king.board_index, &board);
+                    let enemy_attacks = move_generator::gen_enemy_attacks(&friendly_king, team_bitboards, &board, pieces_info);
+                    if let Ok(new_board) = move_generator::new_turn(&piece_move.initial_piece_coordinates, piece_move.final_piece_bit, piece_move.promotion, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, board, pieces_info) {
+                        board = new_board;
+                    }
+                }
+
+                if line_len > 0 && line_len + 1 + text.as_str().len() > PGN_LINE_LEN {
+                    writeln!(pgn)?;
+                    line_len = 0;
+                } else if line_len > 0 {
+                    pgn.write_char(' ')?;
+                    line_len += 1;
+                }
+                pgn.write_str(text.as_str())?;
+                line_len += text.as_str().len();
+            }
+            writeln!(pgn)
+        }
+
+        // Returns the number of times the current position has occured
+        pub fn repetitions(&self) -> usize {
+            let mut repetitions = 0;
+            for i in 0..self.history_len {
+                if self.history[i] == self.board.key {
+                    repetitions += 1;
+                }
+            }
+            repetitions
+        }
+    }
+
+    // Returns true if neither team has enough material to checkmate
+    // K vs K, K and a minor piece vs K, and positions where every bishop is on the same colour square
+    pub fn insufficient_material(board: &Board) -> bool {
+        use crate::bits_on;
+
+        const LIGHT_SQUARES: u64 = 0xAA55AA55AA55AA55;
+
+        // Pawns, rooks, and queens can always checkmate
+        for i in [0, 1, 4, 6, 7, 10] {
+            if board.board[i] != 0 {
+                return false;
+            }
+        }
+
+        let knights = bits_on(board.board[2]) + bits_on(board.board[8]);
+        let bishops = board.board[3] | board.board[9];
+
+        if knights == 0 {
+            return bishops & LIGHT_SQUARES == 0 || bishops & !LIGHT_SQUARES == 0;
+        }
+        knights == 1 && bishops == 0
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::board_representation::{Board, BoardCoordinates, FenError, STARTING_FEN};
+    use super::move_generator::{divide, gen_enemy_attacks, is_legal, is_square_attacked, legal_moves, null_turn, perft, TurnError};
+    use super::game_state::{GameState, insufficient_material};
+    use crate::pgn::GameResult;
+
+    // Node counts from https://www.chessprogramming.org/Perft_Results
+    // Depths are kept low enough for the tests to run quickly
+    fn check_perft(fen: &str, expected_nodes: &[u64]) {
+        let pieces_info = crate::piece::constants::gen();
+        let board = Board::from_fen(fen).unwrap();
+
+        for (i, expected) in expected_nodes.iter().enumerate() {
+            assert_eq!(perft(&board, i + 1, &pieces_info), *expected, "{} at depth {}", fen, i + 1);
+        }
+    }
+
+    #[test]
+    fn perft_starting_position() {
+        check_perft(STARTING_FEN, &[20, 400, 8902, 197281]);
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        check_perft("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", &[48, 2039, 97862]);
+    }
+
+    #[test]
+    fn perft_position_3() {
+        check_perft("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", &[14, 191, 2812, 43238, 674624]);
+    }
+
+    #[test]
+    fn perft_position_4() {
+        check_perft("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", &[6, 264, 9467]);
+    }
+
+    #[test]
+    fn perft_position_5() {
+        check_perft("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", &[44, 1486, 62379]);
+    }
+
+    #[test]
+    fn perft_position_6() {
+        check_perft("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", &[46, 2079, 89890]);
+    }
+
+    #[test]
+    fn perft_illegal_en_passant() {
+        check_perft("3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1", &[18, 92, 1670, 10138, 185429]);
+    }
+
+    #[test]
+    fn perft_en_passant_capture_checks_opponent() {
+        check_perft("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", &[15, 126, 1928, 13931]);
+    }
+
+    #[test]
+    fn perft_castling_gives_check() {
+        check_perft("5k2/8/8/8/8/8/8/4K2R w K - 0 1", &[15, 66, 1198, 6399, 120330]);
+        check_perft("3k4/8/8/8/8/8/8/R3K3 w Q - 0 1", &[16, 71, 1286, 7418, 141077]);
+    }
+
+    #[test]
+    fn perft_castling_rights() {
+        check_perft("r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1", &[26, 1141, 27826]);
+    }
+
+    #[test]
+    fn perft_castling_prevented() {
+        check_perft("r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1", &[44, 1494, 50509]);
+    }
+
+    #[test]
+    fn perft_promotions() {
+        check_perft("2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1", &[11, 133, 1442, 19174]);
+        check_perft("4k3/1P6/8/8/8/8/K7/8 w - - 0 1", &[9, 40, 472, 2661, 38983]);
+        check_perft("8/P1k5/K7/8/8/8/8/8 w - - 0 1", &[6, 27, 273, 1329, 18135]);
+        check_perft("K1k5/8/P7/8/8/8/8/8 w - - 0 1", &[2, 6, 13, 63, 382, 2217]);
+        check_perft("8/k1P5/8/1K6/8/8/8/8 w - - 0 1", &[10, 25, 268, 926, 10857, 43261]);
+        check_perft("8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1", &[29, 165, 5160, 31961]);
+    }
+
+    #[test]
+    fn perft_double_check() {
+        check_perft("8/8/2k5/5q2/5n2/8/5K2/8 b - - 0 1", &[37, 183, 6559, 23527]);
+    }
+
+    #[test]
+    fn legal_moves_are_legal() {
+        let pieces_info = crate::piece::constants::gen();
+
+        // The e2 knight is pinned to the king by the e8 rook
+        let board = Board::from_fen("4r1k1/3P4/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+        let moves = legal_moves(&board, &pieces_info);
+        for piece_move in moves.as_slice() {
+            assert!(is_legal(piece_move, &board, &pieces_info));
+        }
+
+        let mut pinned_move = crate::algorithm::Move::new();
+        pinned_move.initial_piece_coordinates = BoardCoordinates { board_index: 2, bit: crate::ccn_to_bit("e2").unwrap() };
+        pinned_move.final_piece_bit = crate::ccn_to_bit("c3").unwrap();
+        assert!(!is_legal(&pinned_move, &board, &pieces_info));
+
+        // A promotion without a promotion piece is checked as a queen promotion
+        let mut promotion = crate::algorithm::Move::new();
+        promotion.initial_piece_coordinates = BoardCoordinates { board_index: 0, bit: crate::ccn_to_bit("d7").unwrap() };
+        promotion.final_piece_bit = crate::ccn_to_bit("d8").unwrap();
+        assert!(is_legal(&promotion, &board, &pieces_info));
+        promotion.final_piece_bit = crate::ccn_to_bit("e8").unwrap();
+        assert!(is_legal(&promotion, &board, &pieces_info));
+        promotion.final_piece_bit = crate::ccn_to_bit("c8").unwrap();
+        assert!(!is_legal(&promotion, &board, &pieces_info));
+    }
+
+    // The position with the most legal moves, more than the search generates for each position
+    #[test]
+    fn most_legal_moves() {
+        let pieces_info = crate::piece::constants::gen();
+        let board = Board::from_fen("R6R/3Q4/1Q4Q1/4Q3/2Q4Q/Q4Q2/pp1Q4/kBNN1KB1 w - - 0 1").unwrap();
+
+        assert_eq!(legal_moves(&board, &pieces_info).len, 218);
+        assert_eq!(perft(&board, 1, &pieces_info), 218);
+        assert_eq!(divide(&board, 1, &pieces_info).0.len, 218);
+    }
+
+    // Plays moves given in coordinate notation, returns the result of the last move
+    fn play(game: &mut GameState, moves: &[&str]) -> Result<(), TurnError> {
+        let pieces_info = crate::piece::constants::gen();
+
+        let mut result = Ok(());
+        for piece_move in moves {
+            let initial_bit = crate::ccn_to_bit(&piece_move[0..2]).unwrap();
+            let final_bit = crate::ccn_to_bit(&piece_move[2..4]).unwrap();
+
+            let mut found = false;
+            for legal_move in legal_moves(&game.board, &pieces_info).as_slice() {
+                if legal_move.initial_piece_coordinates.bit == initial_bit && legal_move.final_piece_bit == final_bit {
+                    result = game.new_turn(legal_move, &pieces_info);
+                    found = true;
+                    break;
+                }
+            }
+            assert!(found, "{} is not a legal move", piece_move);
+        }
+        result
+    }
+
+    #[test]
+    fn threefold_repetition() {
+        let mut game = GameState::new(Board::from_fen(STARTING_FEN).unwrap());
+
+        assert_eq!(play(&mut game, &["g1f3", "g8f6", "f3g1", "f6g8"]), Ok(()));
+        assert_eq!(game.repetitions(), 2);
+        assert_eq!(play(&mut game, &["g1f3", "g8f6", "f3g1"]), Ok(()));
+        assert_eq!(play(&mut game, &["f6g8"]), Err(TurnError::Draw));
+        assert_eq!(game.repetitions(), 3);
+    }
+
+    #[test]
+    fn repetition_history_resets_after_pawn_move() {
+        let mut game = GameState::new(Board::from_fen(STARTING_FEN).unwrap());
+
+        assert_eq!(play(&mut game, &["g1f3", "g8f6", "f3g1", "f6g8", "e2e4"]), Ok(()));
+        assert_eq!(play(&mut game, &["g8f6", "g1f3", "f6g8", "f3g1"]), Ok(()));
+        assert_eq!(game.repetitions(), 2);
+    }
+
+    #[test]
+    fn dead_positions() {
+        let dead = ["4k3/8/8/8/8/8/8/4K3 w - - 0 1", "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1", "4k3/8/8/8/8/8/8/1N2K3 b - - 0 1", "2b1k3/8/8/8/8/8/8/3BK3 w - - 0 1"];
+        let alive = ["4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", "2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1", "4k3/8/8/8/8/8/8/1NB1K3 w - - 0 1", "1n2k3/8/8/8/8/8/8/1N2K3 w - - 0 1"];
+
+        for fen in dead {
+            assert!(insufficient_material(&Board::from_fen(fen).unwrap()), "{}", fen);
+        }
+        for fen in alive {
+            assert!(!insufficient_material(&Board::from_fen(fen).unwrap()), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn capture_into_dead_position_is_draw() {
+        let mut game = GameState::new(Board::from_fen("4k3/8/8/8/8/8/4r3/4KB2 w - - 0 1").unwrap());
+        assert_eq!(play(&mut game, &["e1e2"]), Err(TurnError::Draw));
+        assert_eq!(game.board.to_fen().as_str(), "4k3/8/8/8/8/8/4K3/5B2 b - - 0 1");
+    }
+
+    #[test]
+    fn pgn_of_recorded_games() {
+        let pieces_info = crate::piece::constants::gen();
+
+        // The mating move ends the game without updating the board, but is still recorded
+        let mut game = GameState::new(Board::from_fen(STARTING_FEN).unwrap());
+        assert_eq!(play(&mut game, &["f2f3", "e7e5", "g2g4", "d8h4"]), Err(TurnError::Win));
+        assert_eq!(game.moves_len(), 4);
+
+        let mut pgn = String::new();
+        game.to_pgn(GameResult::win(false), true, &pieces_info, &mut pgn).unwrap();
+        assert_eq!(pgn, "[Event \"Chess board game\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"-\"]\n[White \"Player\"]\n[Black \"Chess2\"]\n[Result \"0-1\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n");
+
+        // Games from a fen start with its move number
+        let mut game = GameState::new(Board::from_fen("4k3/8/8/8/8/8/8/R3K2R b KQ - 0 12").unwrap());
+        play(&mut game, &["e8d8", "a1a2", "d8e8", "e1g1"]).unwrap();
+
+        let mut pgn = String::new();
+        game.to_pgn(GameResult::Unfinished, false, &pieces_info, &mut pgn).unwrap();
+        assert!(pgn.contains("[White \"Chess2\"]\n[Black \"Player\"]\n[Result \"*\"]\n[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/R3K2R b KQ - 0 12\"]\n\n12... Kd8 13. Ra2 Ke8 14. O-O *\n"), "{}", pgn);
+
+        // Long games are wrapped, repeating positions carries on after the draw
+        let mut game = GameState::new(Board::from_fen(STARTING_FEN).unwrap());
+        for _ in 0..8 {
+            play(&mut game, &["g1f3", "g8f6", "f3g1", "f6g8", "b1c3", "b8c6", "c3b1", "c6b8"]).ok();
+        }
+        assert_eq!(game.moves_len(), 64);
+        let mut pgn = String::new();
+        game.to_pgn(GameResult::Draw, true, &pieces_info, &mut pgn).unwrap();
+        let movetext = pgn.split("\n\n").nth(1).unwrap();
+        assert!(movetext.lines().count() > 1);
+        for line in movetext.lines() {
+            assert!(line.len() <= 80, "{}", line);
+        }
+        assert!(movetext.ends_with("1/2-1/2\n"));
+        assert!(!movetext.contains('{'));
+
+        // Moves after MAX_GAME_MOVES aren't recorded, the PGN notes that they are missing
+        for _ in 0..38 {
+            play(&mut game, &["g1f3", "g8f6", "f3g1", "f6g8", "b1c3", "b8c6", "c3b1", "c6b8"]).ok();
+        }
+        assert_eq!(game.moves_len(), super::game_state::MAX_GAME_MOVES);
+        let mut pgn = String::new();
+        game.to_pgn(GameResult::Draw, true, &pieces_info, &mut pgn).unwrap();
+        assert!(pgn.contains("150. Ng1 Ng8"), "{}", pgn);
+        assert!(pgn.ends_with("{Later moves were not recorded} 1/2-1/2\n"), "{}", pgn);
+    }
+
+    // Checks the incrementally updated key against the recalculated key for every position in the move tree
+    fn check_keys(board: &Board, depth: usize, pieces_info: &[crate::piece::constants::PieceInfo; 12]) {
+        assert_eq!(board.key, board.hash(), "{}", board.to_fen().as_str());
+        if depth == 0 {
+            return;
+        }
+
+        let (friendly_king, enemy_king) = board.kings();
+        let team_bitboards = crate::TeamBitboards::new(friendly_king.board_index, board);
+        let enemy_attacks = super::move_generator::gen_enemy_attacks(&friendly_king, team_bitboards, board, pieces_info);
+
+        for piece_move in legal_moves(board, pieces_info).as_slice() {
+            let new_turn_board = super::move_generator::new_turn(&piece_move.initial_piece_coordinates, piece_move.final_piece_bit, piece_move.promotion, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, *board, pieces_info);
+            if let Ok(new_board) = new_turn_board {
+                check_keys(&new_board, depth - 1, pieces_info);
+            }
+        }
+    }
+
+    #[test]
+    fn incremental_keys_match_hash() {
+        let pieces_info = crate::piece::constants::gen();
+
+        check_keys(&Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap(), 3, &pieces_info);
+        check_keys(&Board::from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap(), 3, &pieces_info);
+        check_keys(&Board::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap(), 4, &pieces_info);
+    }
+
+    // Checks is_square_attacked against the enemy attacks from the move generator for every square, in every position of the move tree
+    fn check_attacks(board: &Board, depth: usize, pieces_info: &[crate::piece::constants::PieceInfo; 12]) {
+        let (friendly_king, _) = board.kings();
+        let team_bitboards = crate::TeamBitboards::new(friendly_king.board_index, board);
+        let enemy_attacks = gen_enemy_attacks(&friendly_king, team_bitboards, board, pieces_info);
+
+        // Enemy attacks from the move generator go through the friendly king, so sliding attacks behind it are included
+        let mut board_without_king = *board;
+        board_without_king.board[friendly_king.board_index] = 0;
+        for bit in 0..64 {
+            assert_eq!(is_square_attacked(bit, !board.whites_move, &board_without_king), crate::bit_on(enemy_attacks.enemy_attack_bitboard, bit), "{} {}", board.to_fen().as_str(), crate::bit_to_ccn(bit));
+        }
+        assert_eq!(board.in_check(), crate::bit_on(enemy_attacks.enemy_attack_bitboard, friendly_king.bit));
+
+        if depth == 0 {
+            return;
+        }
+
+        let (_, enemy_king) = board.kings();
+        for piece_move in legal_moves(board, pieces_info).as_slice() {
+            let new_turn_board = super::move_generator::new_turn(&piece_move.initial_piece_coordinates, piece_move.final_piece_bit, piece_move.promotion, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, *board, pieces_info);
+            if let Ok(new_board) = new_turn_board {
+                check_attacks(&new_board, depth - 1, pieces_info);
+            }
+        }
+    }
+
+    #[test]
+    fn square_attacks_match_enemy_attacks() {
+        let pieces_info = crate::piece::constants::gen();
+
+        check_attacks(&Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap(), 2, &pieces_info);
+        check_attacks(&Board::from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap(), 2, &pieces_info);
+        check_attacks(&Board::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap(), 3, &pieces_info);
+    }
+
+    #[test]
+    fn null_turn_keys_match_hash() {
+        let board = Board::from_fen("rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3").unwrap();
+        let null_board = null_turn(&board);
+        assert!(null_board.whites_move);
+        assert_eq!(null_board.en_passant_target, None);
+        assert_eq!(null_board.key, null_board.hash());
+        assert_eq!(null_turn(&null_board).key, Board::from_fen("rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq - 2 4").unwrap().key);
+    }
+
+    // Keys from the Polyglot opening book format specification
+    #[test]
+    fn polyglot_keys() {
+        let mut game = GameState::new(Board::from_fen(STARTING_FEN).unwrap());
+        assert_eq!(game.board.key, 0x463b96181691fc9c);
+
+        let keys = [0x823c9b50fd114196, 0x0756b94461c50fb0, 0x662fafb965db29d4, 0x22a48b5a8e47ff78, 0x652a607ca3f242c1, 0x00fdd303c946bdd9];
+        let moves = ["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"];
+        for i in 0..moves.len() {
+            play(&mut game, &[moves[i]]).unwrap();
+            assert_eq!(game.board.key, keys[i], "after {}", moves[i]);
+        }
+
+        assert_eq!(Board::from_fen("rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3").unwrap().key, 0x3c8123ea7b067637);
+        assert_eq!(Board::from_fen("rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 0 4").unwrap().key, 0x5c3f9b829b279560);
+    }
+
+    #[test]
+    fn divide_matches_perft() {
+        let pieces_info = crate::piece::constants::gen();
+        let board = Board::from_fen(STARTING_FEN).unwrap();
+
+        let (moves, nodes) = divide(&board, 3, &pieces_info);
+        assert_eq!(moves.len, legal_moves(&board, &pieces_info).len);
+        assert_eq!(nodes.iter().sum::<u64>(), 8902);
+
+        for i in 0..moves.len {
+            let piece_move = moves.moves[i];
+            let expected = match (crate::bit_to_ccn(piece_move.initial_piece_coordinates.bit), crate::bit_to_ccn(piece_move.final_piece_bit)) {
+                ("e2", "e4") => 600,
+                ("g1", "f3") => 440,
+                ("a2", "a3") => 380,
+                _ => continue,
+            };
+            assert_eq!(nodes[i], expected);
+        }
+    }
+
+    #[test]
+    fn fen_round_trip() {
+        let fens = [
+            STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/pp1ppppp/8/2pP4/8/8/PPP1PPPP/RNBQKBNR w KQkq c6 0 3",
+            "4k3/8/8/8/8/8/8/4K2R b K - 5 40",
+        ];
+
+        for fen in fens {
+            assert_eq!(Board::from_fen(fen).unwrap().to_fen().as_str(), fen);
+        }
+    }
+
+    #[test]
+    fn rejects_non_ascii_fens() {
+        assert_eq!(Board::from_fen("rnbqkbnr/pppppppp/8/8/4ƈ3/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), Err(FenError::InvalidPiece('ƈ')));
+        assert_eq!(Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq ťĴ 0 1"), Err(FenError::InvalidEnPassant));
+        assert_eq!(crate::ccn_to_bit("ťĴ"), None);
+    }
+}
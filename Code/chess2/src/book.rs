@@ -0,0 +1,276 @@
+use crate::algorithm::Move;
+use crate::board::board_representation;
+use crate::strength::Random;
+
+// Polyglot books are a list of 16 byte entries sorted by key
+// Each entry is a big endian zobrist key (8 bytes), move (2 bytes), weight (2 bytes) and learn value (4 bytes, unused)
+pub const ENTRY_BYTES: usize = 16;
+
+// Board indexes (relative to the teams pawn index) of the Polyglot promotion pieces, the promotion value 0 is no promotion
+// Knight, bishop, rook, queen
+const PROMOTION_INDEXES: [usize; 4] = [2, 3, 1, 4];
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Entry {
+    pub key: u64, // Polyglot key of the position, the same as Board::key
+    pub book_move: u16, // Move in the Polyglot format, see decode_move
+    pub weight: u16, // How often the move should be played relative to the other moves of the position
+}
+
+// Errors that can be encountered when reading a book
+#[derive(PartialEq, Debug)]
+pub enum BookError {
+    PartialEntry, // The length of the data isn't a whole number of entries
+}
+
+// Opening book read from the bytes of a Polyglot .bin file
+// The bytes aren't copied, so a book included with include_bytes! stays in flash
+pub struct Book<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Book<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, BookError> {
+        if !data.len().is_multiple_of(ENTRY_BYTES) {
+            return Err(BookError::PartialEntry);
+        }
+
+        Ok(Book { data })
+    }
+
+    // Number of entries in the book
+    pub fn len(&self) -> usize {
+        self.data.len() / ENTRY_BYTES
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn entry(&self, index: usize) -> Entry {
+        let bytes = &self.data[index * ENTRY_BYTES..(index + 1) * ENTRY_BYTES];
+
+        let mut key = 0;
+        for &byte in &bytes[..8] {
+            key = (key << 8) | byte as u64;
+        }
+
+        Entry {
+            key,
+            book_move: u16::from_be_bytes([bytes[8], bytes[9]]),
+            weight: u16::from_be_bytes([bytes[10], bytes[11]]),
+        }
+    }
+
+    // Returns the index of the first entry with the key (or where it would be), using a binary search since entries are sorted by key
+    fn first_index(&self, key: u64) -> usize {
+        let mut low = 0;
+        let mut high = self.len();
+        while low < high {
+            let middle = (low + high) / 2;
+            if self.entry(middle).key < key {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        low
+    }
+
+    // Picks a book move for the board, moves with a higher weight are more likely to be picked
+    // Returns None if the position isn't in the book, or the picked move isn't legal
+    pub fn pick_move(&self, board: &board_representation::Board, random: &mut Random, pieces_info: &[crate::piece::constants::PieceInfo; 12]) -> Option<Move> {
+        let first_index = self.first_index(board.key);
+
+        let mut total_weight: u64 = 0;
+        let mut index = first_index;
+        while index < self.len() && self.entry(index).key == board.key {
+            total_weight += self.entry(index).weight as u64;
+            index += 1;
+        }
+
+        if total_weight == 0 {
+            return None;
+        }
+
+        let mut picked_weight = random.below(total_weight);
+        for i in first_index..index {
+            let entry = self.entry(i);
+            if picked_weight < entry.weight as u64 {
+                return decode_move(entry.book_move, board, pieces_info);
+            }
+            picked_weight -= entry.weight as u64;
+        }
+
+        None
+    }
+}
+
+// Polyglot squares count files from a and rows from rank 1, while board bits start at a8
+fn square_to_bit(square: u16) -> usize {
+    let file = (square & 7) as usize;
+    let row = ((square >> 3) & 7) as usize;
+    (7 - row) * 8 + file
+}
+
+fn bit_to_square(bit: usize) -> u16 {
+    let file = (bit % 8) as u16;
+    let row = (7 - bit / 8) as u16;
+    (row << 3) | file
+}
+
+// Board index of the pawn of the team to move, the board indexes of the other pieces are relative to it
+fn team_offset(board: &board_representation::Board) -> usize {
+    if board.whites_move {
+        0
+    } else {
+        6
+    }
+}
+
+// Finds the legal move for a Polyglot move
+// Bits 0-5 are the final square, bits 6-11 the initial square and bits 12-14 the promotion piece
+// Castling is stored as the king moving to the square of the rook it castles with
+pub fn decode_move(book_move: u16, board: &board_representation::Board, pieces_info: &[crate::piece::constants::PieceInfo; 12]) -> Option<Move> {
+    use crate::board::move_generator;
+
+    let initial_bit = square_to_bit((book_move >> 6) & 63);
+    let mut final_bit = square_to_bit(book_move & 63);
+
+    let promotion_piece = ((book_move >> 12) & 7) as usize;
+    let promotion = if promotion_piece == 0 || promotion_piece > PROMOTION_INDEXES.len() {
+        None
+    } else {
+        Some(PROMOTION_INDEXES[promotion_piece - 1] + team_offset(board))
+    };
+
+    // A king moving more than one file is castling, the king moves two files towards the rook
+    let king_index = 5 + team_offset(board);
+    let initial_file = initial_bit % 8;
+    let final_file = final_bit % 8;
+    if crate::bit_on(board.board[king_index], initial_bit) && initial_bit / 8 == final_bit / 8 && initial_file == 4 {
+        if final_file == 7 {
+            final_bit = initial_bit + 2;
+        } else if final_file == 0 {
+            final_bit = initial_bit - 2;
+        }
+    }
+
+    let moves = move_generator::legal_moves(board, pieces_info);
+    for i in 0..moves.len {
+        let piece_move = moves.moves[i];
+        if piece_move.initial_piece_coordinates.bit == initial_bit && piece_move.final_piece_bit == final_bit && piece_move.promotion == promotion {
+            return Some(piece_move);
+        }
+    }
+
+    None
+}
+
+// Converts a move to the Polyglot format, the opposite of decode_move
+pub fn encode_move(piece_move: &Move, board: &board_representation::Board) -> u16 {
+    let initial_bit = piece_move.initial_piece_coordinates.bit;
+    let mut final_bit = piece_move.final_piece_bit;
+
+    // Castling moves the king to the square of the rook
+    if piece_move.initial_piece_coordinates.board_index == 5 + team_offset(board) {
+        if final_bit == initial_bit + 2 {
+            final_bit = initial_bit + 3;
+        } else if final_bit + 2 == initial_bit {
+            final_bit = initial_bit - 4;
+        }
+    }
+
+    let mut promotion_piece = 0;
+    if let Some(promotion_index) = piece_move.promotion {
+        for (i, &index) in PROMOTION_INDEXES.iter().enumerate() {
+            if index == promotion_index % 6 {
+                promotion_piece = i as u16 + 1;
+            }
+        }
+    }
+
+    (promotion_piece << 12) | (bit_to_square(initial_bit) << 6) | bit_to_square(final_bit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::board_representation::{Board, STARTING_FEN};
+
+    fn book_bytes(entries: &[(u64, u16, u16)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (key, book_move, weight) in entries {
+            bytes.extend_from_slice(&key.to_be_bytes());
+            bytes.extend_from_slice(&book_move.to_be_bytes());
+            bytes.extend_from_slice(&weight.to_be_bytes());
+            bytes.extend_from_slice(&[0; 4]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn decodes_polyglot_moves() {
+        let pieces_info = crate::piece::constants::gen();
+
+        // e2e4 from the start position
+        let board = Board::from_fen(STARTING_FEN).unwrap();
+        let piece_move = decode_move(0x031C, &board, &pieces_info).unwrap();
+        assert_eq!(crate::bit_to_ccn(piece_move.initial_piece_coordinates.bit), "e2");
+        assert_eq!(crate::bit_to_ccn(piece_move.final_piece_bit), "e4");
+        assert_eq!(encode_move(&piece_move, &board), 0x031C);
+
+        // Black castling queen side is stored as e8a8
+        let board = Board::from_fen("r3k3/8/8/8/8/8/8/4K3 b q - 0 1").unwrap();
+        let piece_move = decode_move(0x0F38, &board, &pieces_info).unwrap();
+        assert_eq!(crate::bit_to_ccn(piece_move.final_piece_bit), "c8");
+        assert_eq!(encode_move(&piece_move, &board), 0x0F38);
+
+        // a7a8 promoting to a knight
+        let board = Board::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let piece_move = decode_move(0x1C38, &board, &pieces_info).unwrap();
+        assert_eq!(piece_move.promotion, Some(2));
+        assert_eq!(encode_move(&piece_move, &board), 0x1C38);
+    }
+
+    #[test]
+    fn picks_weighted_moves() {
+        let pieces_info = crate::piece::constants::gen();
+        let board = Board::from_fen(STARTING_FEN).unwrap();
+
+        // e2e4 and d2d4 for the start position, and an entry for another position on either side
+        let bytes = book_bytes(&[(board.key - 1, 0x031C, 1), (board.key, 0x031C, 3), (board.key, 0x02DB, 1), (board.key + 1, 0x0000, 1)]);
+        let book = Book::new(&bytes).unwrap();
+        assert_eq!(book.len(), 4);
+
+        let mut random = Random::new(1);
+        let mut e4_picks = 0;
+        for _ in 0..400 {
+            let piece_move = book.pick_move(&board, &mut random, &pieces_info).unwrap();
+            if crate::bit_to_ccn(piece_move.final_piece_bit) == "e4" {
+                e4_picks += 1;
+            } else {
+                assert_eq!(crate::bit_to_ccn(piece_move.final_piece_bit), "d4");
+            }
+        }
+        assert!(e4_picks > 250 && e4_picks < 350);
+
+        let other_board = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(book.pick_move(&other_board, &mut random, &pieces_info), None);
+
+        assert_eq!(Book::new(&bytes[1..]).err(), Some(BookError::PartialEntry));
+    }
+
+    #[test]
+    fn embedded_book_is_sorted() {
+        let pieces_info = crate::piece::constants::gen();
+        let book = Book::new(include_bytes!("../opening_book.bin")).unwrap();
+
+        for i in 1..book.len() {
+            assert!(book.entry(i - 1).key <= book.entry(i).key);
+        }
+
+        let board = Board::from_fen(STARTING_FEN).unwrap();
+        assert!(book.pick_move(&board, &mut Random::new(1), &pieces_info).is_some());
+    }
+}
@@ -0,0 +1,418 @@
+use crate::bit_on;
+use crate::board::board_representation::Board;
+
+// Piece values and piece square tables are from PeSTO
+// Each score has a middlegame and endgame value, the final score is blended between them depending on the material left on the board
+// Tables are from the white team perspective with bit 0 as a8, the same as the board representation
+// Pieces are in board index order (pawn, rook, knight, bishop, queen, king)
+
+const MG_VALUES: [i32; 6] = [82, 477, 337, 365, 1025, 0];
+const EG_VALUES: [i32; 6] = [94, 512, 281, 297, 936, 0];
+
+// How much each piece counts towards the game phase, 24 is a full middlegame
+const PHASE_VALUES: [i32; 6] = [0, 2, 1, 1, 4, 0];
+const MAX_PHASE: i32 = 24;
+
+const MG_TABLES: [[i16; 64]; 6] = [
+    [ // Pawn
+          0,   0,   0,   0,   0,   0,   0,   0,
+         98, 134,  61,  95,  68, 126,  34, -11,
+         -6,   7,  26,  31,  65,  56,  25, -20,
+        -14,  13,   6,  21,  23,  12,  17, -23,
+        -27,  -2,  -5,  12,  17,   6,  10, -25,
+        -26,  -4,  -4, -10,   3,   3,  33, -12,
+        -35,  -1, -20, -23, -15,  24,  38, -22,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    [ // Rook
+         32,  42,  32,  51,  63,   9,  31,  43,
+         27,  32,  58,  62,  80,  67,  26,  44,
+         -5,  19,  26,  36,  17,  45,  61,  16,
+        -24, -11,   7,  26,  24,  35,  -8, -20,
+        -36, -26, -12,  -1,   9,  -7,   6, -23,
+        -45, -25, -16, -17,   3,   0,  -5, -33,
+        -44, -16, -20,  -9,  -1,  11,  -6, -71,
+        -19, -13,   1,  17,  16,   7, -37, -26,
+    ],
+    [ // Knight
+       -167, -89, -34, -49,  61, -97, -15,-107,
+        -73, -41,  72,  36,  23,  62,   7, -17,
+        -47,  60,  37,  65,  84, 129,  73,  44,
+         -9,  17,  19,  53,  37,  69,  18,  22,
+        -13,   4,  16,  13,  28,  19,  21,  -8,
+        -23,  -9,  12,  10,  19,  17,  25, -16,
+        -29, -53, -12,  -3,  -1,  18, -14, -19,
+       -105, -21, -58, -33, -17, -28, -19, -23,
+    ],
+    [ // Bishop
+        -29,   4, -82, -37, -25, -42,   7,  -8,
+        -26,  16, -18, -13,  30,  59,  18, -47,
+        -16,  37,  43,  40,  35,  50,  37,  -2,
+         -4,   5,  19,  50,  37,  37,   7,  -2,
+         -6,  13,  13,  26,  34,  12,  10,   4,
+          0,  15,  15,  15,  14,  27,  18,  10,
+          4,  15,  16,   0,   7,  21,  33,   1,
+        -33,  -3, -14, -21, -13, -12, -39, -21,
+    ],
+    [ // Queen
+        -28,   0,  29,  12,  59,  44,  43,  45,
+        -24, -39,  -5,   1, -16,  57,  28,  54,
+        -13, -17,   7,   8,  29,  56,  47,  57,
+        -27, -27, -16, -16,  -1,  17,  -2,   1,
+         -9, -26,  -9, -10,  -2,  -4,   3,  -3,
+        -14,   2, -11,  -2,  -5,   2,  14,   5,
+        -35,  -8,  11,   2,   8,  15,  -3,   1,
+         -1, -18,  -9,  10, -15, -25, -31, -50,
+    ],
+    [ // King
+        -65,  23,  16, -15, -56, -34,   2,  13,
+         29,  -1, -20,  -7,  -8,  -4, -38, -29,
+         -9,  24,   2, -16, -20,   6,  22, -22,
+        -17, -20, -12, -27, -30, -25, -14, -36,
+        -49,  -1, -27, -39, -46, -44, -33, -51,
+        -14, -14, -22, -46, -44, -30, -15, -27,
+          1,   7,  -8, -64, -43, -16,   9,   8,
+        -15,  36,  12, -54,   8, -28,  24,  14,
+    ],
+];
+
+const EG_TABLES: [[i16; 64]; 6] = [
+    [ // Pawn
+          0,   0,   0,   0,   0,   0,   0,   0,
+        178, 173, 158, 134, 147, 132, 165, 187,
+         94, 100,  85,  67,  56,  53,  82,  84,
+         32,  24,  13,   5,  -2,   4,  17,  17,
+         13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+          4,   7,  -6,   1,   0,  -5,  -1,  -8,
+         13,   8,   8,  10,  13,   0,   2,  -7,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    [ // Rook
+         13,  10,  18,  15,  12,  12,   8,   5,
+         11,  13,  13,  11,  -3,   3,   8,   3,
+          7,   7,   7,   5,   4,  -3,  -5,  -3,
+          4,   3,  13,   1,   2,   1,  -1,   2,
+          3,   5,   8,   4,  -5,  -6,  -8, -11,
+         -4,   0,  -5,  -1,  -7, -12,  -8, -16,
+         -6,  -6,   0,   2,  -9,  -9, -11,  -3,
+         -9,   2,   3,  -1,  -5, -13,   4, -20,
+    ],
+    [ // Knight
+        -58, -38, -13, -28, -31, -27, -63, -99,
+        -25,  -8, -25,  -2,  -9, -25, -24, -52,
+        -24, -20,  10,   9,  -1,  -9, -19, -41,
+        -17,   3,  22,  22,  22,  11,   8, -18,
+        -18,  -6,  16,  25,  16,  17,   4, -18,
+        -23,  -3,  -1,  15,  10,  -3, -20, -22,
+        -42, -20, -10,  -5,  -2, -20, -23, -44,
+        -29, -51, -23, -15, -22, -18, -50, -64,
+    ],
+    [ // Bishop
+        -14, -21, -11,  -8,  -7,  -9, -17, -24,
+         -8,  -4,   7, -12,  -3, -13,  -4, -14,
+          2,  -8,   0,  -1,  -2,   6,   0,   4,
+         -3,   9,  12,   9,  14,  10,   3,   2,
+         -6,   3,  13,  19,   7,  10,  -3,  -9,
+        -12,  -3,   8,  10,  13,   3,  -7, -15,
+        -14, -18,  -7,  -1,   4,  -9, -15, -27,
+        -23,  -9, -23,  -5,  -9, -16,  -5, -17,
+    ],
+    [ // Queen
+         -9,  22,  22,  27,  27,  19,  10,  20,
+        -17,  20,  32,  41,  58,  25,  30,   0,
+        -20,   6,   9,  49,  47,  35,  19,   9,
+          3,  22,  24,  45,  57,  40,  57,  36,
+        -18,  28,  19,  47,  31,  34,  39,  23,
+        -16, -27,  15,   6,   9,  17,  10,   5,
+        -22, -23, -30, -16, -16, -23, -36, -32,
+        -33, -28, -22, -43,  -5, -32, -20, -41,
+    ],
+    [ // King
+        -74, -35, -18, -18, -11,  15,   4, -17,
+        -12,  17,  14,  17,  17,  38,  23,  11,
+         10,  17,  23,  15,  20,  45,  44,  13,
+         -8,  22,  24,  27,  26,  33,  26,   3,
+        -18,  -4,  21,  24,  27,  23,   9, -11,
+        -19,  -3,  11,  21,  23,  16,   7,  -9,
+        -27, -11,   4,  13,  14,   4,  -5, -17,
+        -53, -34, -21, -11, -28, -14, -24, -43,
+    ],
+];
+
+// Pawn structure (middlegame, endgame)
+const DOUBLED_PAWN: (i32, i32) = (-10, -20); // For every extra pawn on a file
+const ISOLATED_PAWN: (i32, i32) = (-10, -15); // Pawns with no friendly pawns on the neighbouring files
+
+// Bonus for passed pawns, indexed by how many rows the pawn has moved from its starting row
+const PASSED_PAWN_MG: [i32; 7] = [0, 5, 5, 10, 20, 35, 55];
+const PASSED_PAWN_EG: [i32; 7] = [0, 10, 15, 25, 40, 65, 100];
+
+// King safety, only used in the middlegame
+const PAWN_SHIELD: [i32; 2] = [12, 6]; // Friendly pawns one and two rows in front of the king
+const OPEN_FILE_NEAR_KING: i32 = -15; // Files next to the king without friendly pawns
+
+// Bonus for every square a piece can move to (middlegame, endgame), in board index order
+const MOBILITY: [(i32, i32); 6] = [(0, 0), (2, 4), (4, 4), (5, 5), (1, 2), (0, 0)];
+
+// Row and column changes for piece moves
+const KNIGHT_MOVES: [(i8, i8); 8] = [(-2, -1), (-2, 1), (-1, 2), (1, 2), (2, 1), (2, -1), (1, -2), (-1, -2)];
+const STRAIGHT_MOVES: [(i8, i8); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+const DIAGONAL_MOVES: [(i8, i8); 4] = [(-1, -1), (-1, 1), (1, 1), (1, -1)];
+
+// Returns the static evaluation of the board in centipawns
+// The score is from the perspective of the team to move, positive scores are good for the team to move
+pub fn evaluate(board: &Board) -> i16 {
+    let white_pieces = or_bitboards(board, 0);
+    let black_pieces = or_bitboards(board, 6);
+    let all_pieces = white_pieces | black_pieces;
+
+    let mut mg = 0; // White middlegame score minus black middlegame score
+    let mut eg = 0; // White endgame score minus black endgame score
+    let mut phase = 0;
+
+    for i in 0..12 {
+        if board.board[i] == 0 {
+            continue;
+        }
+
+        let piece = i % 6;
+        let white = i < 6;
+
+        let sign;
+        let friendly_pieces;
+        if white {
+            sign = 1;
+            friendly_pieces = white_pieces;
+        } else {
+            sign = -1;
+            friendly_pieces = black_pieces;
+        }
+
+        for bit in 0..64 {
+            if !bit_on(board.board[i], bit) {
+                continue;
+            }
+
+            // Black uses the white tables flipped vertically
+            let table_bit = if white { bit } else { bit ^ 56 };
+
+            mg += sign * (MG_VALUES[piece] + MG_TABLES[piece][table_bit] as i32);
+            eg += sign * (EG_VALUES[piece] + EG_TABLES[piece][table_bit] as i32);
+            phase += PHASE_VALUES[piece];
+
+            // Mobility
+            let moves = match piece {
+                1 => count_moves(bit, &STRAIGHT_MOVES, true, friendly_pieces, all_pieces),
+                2 => count_moves(bit, &KNIGHT_MOVES, false, friendly_pieces, all_pieces),
+                3 => count_moves(bit, &DIAGONAL_MOVES, true, friendly_pieces, all_pieces),
+                4 => count_moves(bit, &STRAIGHT_MOVES, true, friendly_pieces, all_pieces) + count_moves(bit, &DIAGONAL_MOVES, true, friendly_pieces, all_pieces),
+                _ => 0,
+            };
+            mg += sign * MOBILITY[piece].0 * moves;
+            eg += sign * MOBILITY[piece].1 * moves;
+        }
+    }
+
+    let (white_pawns_mg, white_pawns_eg) = pawn_structure(board, true);
+    let (black_pawns_mg, black_pawns_eg) = pawn_structure(board, false);
+    mg += white_pawns_mg - black_pawns_mg;
+    eg += white_pawns_eg - black_pawns_eg;
+
+    mg += king_safety(board, true) - king_safety(board, false);
+
+    // Blend the middlegame and endgame scores depending on the game phase
+    // Promotions can take the phase over the maximum
+    if phase > MAX_PHASE {
+        phase = MAX_PHASE;
+    }
+    let mut score = (mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE;
+
+    if !board.whites_move {
+        score *= -1;
+    }
+    score.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+// Returns a bitboard of every piece on a team, index is the pawn board index of the team
+fn or_bitboards(board: &Board, index: usize) -> u64 {
+    let mut bitboard = 0;
+    for i in index..index + 6 {
+        bitboard |= board.board[i];
+    }
+    bitboard
+}
+
+// Counts the squares a piece can move to, including captures
+fn count_moves(bit: usize, directions: &[(i8, i8)], sliding: bool, friendly_pieces: u64, all_pieces: u64) -> i32 {
+    let row = (bit / 8) as i8;
+    let column = (bit % 8) as i8;
+
+    let mut moves = 0;
+    for &(row_change, column_change) in directions {
+        let mut new_row = row + row_change;
+        let mut new_column = column + column_change;
+        while (0..8).contains(&new_row) && (0..8).contains(&new_column) {
+            let new_bit = (new_row * 8 + new_column) as usize;
+
+            if !bit_on(friendly_pieces, new_bit) {
+                moves += 1;
+            }
+
+            // Pieces can't move through other pieces
+            if !sliding || bit_on(all_pieces, new_bit) {
+                break;
+            }
+
+            new_row += row_change;
+            new_column += column_change;
+        }
+    }
+    moves
+}
+
+// Returns the (middlegame, endgame) score for the pawn structure of a team
+fn pawn_structure(board: &Board, white: bool) -> (i32, i32) {
+    let friendly_pawns;
+    let enemy_pawns;
+    if white {
+        friendly_pawns = board.board[0];
+        enemy_pawns = board.board[6];
+    } else {
+        friendly_pawns = board.board[6];
+        enemy_pawns = board.board[0];
+    }
+
+    // Count pawns on each file
+    let mut file_pawns = [0; 8];
+    for bit in 0..64 {
+        if bit_on(friendly_pawns, bit) {
+            file_pawns[bit % 8] += 1;
+        }
+    }
+
+    let mut mg = 0;
+    let mut eg = 0;
+
+    for pawns in file_pawns {
+        if pawns > 1 {
+            mg += DOUBLED_PAWN.0 * (pawns - 1);
+            eg += DOUBLED_PAWN.1 * (pawns - 1);
+        }
+    }
+
+    for bit in 0..64 {
+        if !bit_on(friendly_pawns, bit) {
+            continue;
+        }
+
+        let row = bit / 8;
+        let file = bit % 8;
+
+        let left_pawns = file > 0 && file_pawns[file - 1] > 0;
+        let right_pawns = file < 7 && file_pawns[file + 1] > 0;
+        if !left_pawns && !right_pawns {
+            mg += ISOLATED_PAWN.0;
+            eg += ISOLATED_PAWN.1;
+        }
+
+        // A pawn is passed if no enemy pawns are in front of it on the same or neighbouring files
+        let mut passed = true;
+        for enemy_bit in 0..64 {
+            if !bit_on(enemy_pawns, enemy_bit) {
+                continue;
+            }
+
+            let enemy_row = enemy_bit / 8;
+            let enemy_file = enemy_bit % 8;
+
+            let in_front = if white { enemy_row < row } else { enemy_row > row };
+
+            if in_front && (enemy_file as i8 - file as i8).abs() <= 1 {
+                passed = false;
+                break;
+            }
+        }
+
+        if passed {
+            let rows_moved = if white { 6 - row } else { row - 1 };
+            mg += PASSED_PAWN_MG[rows_moved];
+            eg += PASSED_PAWN_EG[rows_moved];
+        }
+    }
+
+    (mg, eg)
+}
+
+// Returns the middlegame king safety score for a team, from the pawns shielding the king
+fn king_safety(board: &Board, white: bool) -> i32 {
+    let king_bit;
+    let friendly_pawns;
+    let forward: i8; // Row change for moving towards the enemy side
+    if white {
+        king_bit = crate::find_bit_on(board.board[5], 0);
+        friendly_pawns = board.board[0];
+        forward = -1;
+    } else {
+        king_bit = crate::find_bit_on(board.board[11], 0);
+        friendly_pawns = board.board[6];
+        forward = 1;
+    }
+
+    let king_row = (king_bit / 8) as i8;
+    let king_file = (king_bit % 8) as i8;
+
+    let mut score = 0;
+    for file in (king_file - 1)..=(king_file + 1) {
+        if !(0..=7).contains(&file) {
+            continue;
+        }
+
+        // Pawns directly in front of the king
+        for (i, shield) in PAWN_SHIELD.iter().enumerate() {
+            let row = king_row + forward * (i as i8 + 1);
+            if (0..8).contains(&row) && bit_on(friendly_pawns, (row * 8 + file) as usize) {
+                score += shield;
+            }
+        }
+
+        // Files without friendly pawns let enemy rooks and queens attack the king
+        let mut file_has_pawn = false;
+        for row in 0..8 {
+            if bit_on(friendly_pawns, (row * 8 + file) as usize) {
+                file_has_pawn = true;
+                break;
+            }
+        }
+        if !file_has_pawn {
+            score += OPEN_FILE_NEAR_KING;
+        }
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::evaluate;
+    use crate::board::board_representation::{Board, STARTING_FEN};
+
+    #[test]
+    fn starting_position_is_equal() {
+        assert_eq!(evaluate(&Board::from_fen(STARTING_FEN).unwrap()), 0);
+    }
+
+    #[test]
+    fn mirrored_positions_are_equal() {
+        let white = Board::from_fen("r1bqk2r/ppp2ppp/2n2n2/3pp3/1bPP4/2N1PN2/PP3PPP/R1BQKB1R w KQkq - 0 6").unwrap();
+        let black = Board::from_fen("r1bqkb1r/pp3ppp/2n1pn2/1Bpp4/3PP3/2N2N2/PPP2PPP/R1BQK2R b KQkq - 0 6").unwrap();
+        assert_eq!(evaluate(&white), evaluate(&black));
+    }
+
+    #[test]
+    fn material_advantage() {
+        let board = Board::from_fen("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert!(evaluate(&board) > 800);
+
+        let board = Board::from_fen("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1").unwrap();
+        assert!(evaluate(&board) < -800);
+    }
+}
@@ -1,21 +1,32 @@
-#![no_std]
-#![no_main]
+#![cfg_attr(not(test), no_std)]
 
 use core::convert::TryFrom;
 use core::convert::TryInto;
 
 use core::result::Result::Ok;
 use core::result::Result::Err;
-use rtt_target::{rprintln, rtt_init_print};
 
 pub mod board;
 pub mod piece;
 pub mod algorithm;
+pub mod zobrist;
+pub mod transposition;
+pub mod evaluation;
+pub mod time_management;
+pub mod strength;
+pub mod book;
+pub mod notation;
+pub mod pgn;
 
 // Convert a char of a number to an integer
 // E.g. '1' -> 1
 // Offset offsets the ascii value
+#[allow(clippy::result_unit_err, clippy::manual_range_contains)]
 pub fn char_to_num(c: char, offset: i8) -> Result<i8, ()> {
+    // Only ascii chars fit in an i8
+    if !c.is_ascii() {
+        return Err(());
+    }
     let num = c as i8 - {48 + offset};
     if num < 0 || num > 9 {
         return Err(())
@@ -23,6 +34,7 @@ pub fn char_to_num(c: char, offset: i8) -> Result<i8, ()> {
     Ok(num)
 }
 
+#[allow(clippy::result_unit_err)]
 pub fn num_to_char(num: usize) -> Result<char, ()> {
     if num > 9 {
         return Err(());
@@ -46,6 +58,29 @@ pub fn bit_to_ccn(bit: usize) -> &'static str {
     ccn_array[bit]
 }
 
+// Converts a square in chess coordinate notation to a bit
+// E.g. "a8" -> 0
+// Returns None if the text isn't a square
+#[allow(clippy::manual_range_contains)]
+pub fn ccn_to_bit(ccn: &str) -> Option<usize> {
+    let mut chars = ccn.chars();
+
+    let file = char_to_num(chars.next()?, 49).ok()?; // 'a' is 49 places after '0' in ascii
+    let rank = char_to_num(chars.next()?, 0).ok()?;
+
+    // Only 2 characters are allowed
+    if chars.next().is_some() {
+        return None;
+    }
+
+    if file > 7 || rank < 1 || rank > 8 {
+        return None;
+    }
+
+    // y is flipped on the board representation so rank 8 is at the top
+    Some(((8 - rank) * 8 + file) as usize)
+}
+
 // Converts a bit number (e.g. bit 7 in a u64) to a cartesian coordinates on the board
 fn bit_to_cartesian(bit: i8) -> [i8; 2] {
     [bit % 8, bit / 8]
@@ -72,6 +107,7 @@ pub fn bit_move_valid(initial_bit: usize, delta_bit: i8) -> bool {
 }
 
 // Ors a group of bitboards in a board array
+#[allow(clippy::needless_range_loop)]
 pub fn or_bitboards(from: usize, to: usize, board: &[u64; 13]) -> u64 {
     let mut bitboard = 0;
     for i in from..to + 1 {
@@ -121,6 +157,7 @@ pub fn find_bit_on(num: u64, default: usize) -> usize {
 }
 
 // Finds the bitboard index for a piece at a given bit
+#[allow(clippy::result_unit_err)]
 pub fn find_board_index(board: &board::board_representation::Board, bit: usize) -> Result<usize, ()> {
     for i in 0..(board.board.len() - 1) {
         if bit_on(board.board[i], bit) {
@@ -169,6 +206,7 @@ pub fn find_piece_change(init_bitboard: u64, final_bitboard: u64) -> i8 {
 
 // Finds a piece that has moved given a final and initial bitboard
 // Returns an error if more than one piece was moved
+#[allow(clippy::needless_return)]
 pub fn find_bitboard_move(init_bitboard: u64, final_bitboard: u64, init_board: &board::board_representation::Board, player_white: bool) -> Result<algorithm::Move, u8> {
     // Return an error if pieces were removed from the board
     if bits_on(init_bitboard) != bits_on(final_bitboard) {
@@ -250,6 +288,9 @@ impl TeamBitboards {
     }
 }
 
+// Hardware for the chess board, only available with the embedded feature
+#[cfg(feature = "embedded")]
+#[allow(clippy::collapsible_match, clippy::needless_return, clippy::new_without_default, clippy::single_match)]
 pub mod embedded {
     use super::*;
 
@@ -414,6 +455,16 @@ pub mod embedded {
                 self.cycles = (self.cycle_resets as u64 * u32::MAX as u64) + dwt_cycles as u64; // Update cycle count
             }
         }
+
+        impl crate::algorithm::CycleCounter for Counter {
+            fn update(&mut self) {
+                Counter::update(self);
+            }
+
+            fn cycles(&self) -> u64 {
+                self.cycles
+            }
+        }
     }
 
     pub mod button {
@@ -485,6 +536,54 @@ pub mod embedded {
         }
     }
 
+    // Cycle counter used to search while the player is thinking (pondering)
+    // The hall sensors and button are checked between search nodes, when either changes the search is stopped by returning the maximum cycle count
+    // This lets the search end as soon as the player starts their move, so the board and button are still responsive
+    pub mod ponder_counter {
+        use super::*;
+
+        pub struct PonderCounter<'a, T: InputPin> {
+            pub counter: &'a mut cycle_counter::Counter,
+            pub grid_sr: &'a mut ShiftRegister,
+            pub hall_sensor: &'a T,
+            pub delay: &'a mut Delay,
+            pub button: &'a button::Button,
+            pub physical_bitboard: u64, // Bitboard of the physical board when the search started
+            pub last_scan_cycle: u64, // Processor cycles elapsed when the hall sensors and button were last checked
+            pub scan_cycles: u64, // Cycles between checking the hall sensors and button
+            pub stop_cycle: u64, // Cycle the search is interrupted at, so the player loop can update the clock
+            pub interrupted: bool, // True once the board or button has changed, or the stop cycle has passed
+        }
+
+        impl<'a, T: InputPin> crate::algorithm::CycleCounter for PonderCounter<'a, T> {
+            fn update(&mut self) {
+                self.counter.update();
+
+                if self.counter.cycles >= self.stop_cycle {
+                    self.interrupted = true;
+                }
+
+                if self.interrupted || self.counter.cycles - self.last_scan_cycle < self.scan_cycles {
+                    return;
+                }
+                self.last_scan_cycle = self.counter.cycles;
+
+                // Any change to the button pin is handled by the player loop, so presses and releases both stop the search
+                let physical_bitboard = read_board_halls(self.grid_sr, self.hall_sensor, self.delay);
+                if physical_bitboard != self.physical_bitboard || digital_read(&self.button.pin) != self.button.press_raw {
+                    self.interrupted = true;
+                }
+            }
+
+            fn cycles(&self) -> u64 {
+                if self.interrupted {
+                    return u64::MAX;
+                }
+                self.counter.cycles
+            }
+        }
+    }
+
     pub mod character_lcd {
         use super::*;
 
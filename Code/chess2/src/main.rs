@@ -3,6 +3,8 @@
 
 use panic_halt as _;
 
+use core::mem::MaybeUninit;
+
 use cortex_m_rt::entry;
 use embedded_hal::digital::v2::InputPin;
 use stm32f1xx_hal as hal;
@@ -10,13 +12,22 @@ use hal::{pac, delay::Delay, prelude::*};
 
 use arrform::{arrform, ArrForm};
 
-use rtt_target::{rprintln, rtt_init_print};
+use rtt_target::{rtt_init_print, rprint, rprintln};
 
 
 use chess2::board::board_representation;
+use chess2::board::game_state::{GameState, insufficient_material};
 use chess2::algorithm;
+use chess2::transposition::{TranspositionTable, EMBEDDED_ENTRIES};
+use chess2::time_management::{ChessClock, TimeLimits};
+use chess2::strength::{EvalNoise, Random, STRENGTH_LEVELS};
+use chess2::pgn::GameResult;
 use chess2::embedded;
 
+mod opening_heatmap;
+
+// Lints for the code style of the original firmware, e.g. late initialisation of variables with if/else blocks
+#[allow(clippy::needless_late_init, clippy::redundant_field_names)]
 #[entry]
 fn main() -> ! {
     // Init buffers for debug printing
@@ -95,25 +106,28 @@ fn main() -> ! {
     };
 
     // Initiliaze board to starting board
-    let starting_board = board_representation::Board {
-        board: [71776119061217280, 9295429630892703744, 4755801206503243776, 2594073385365405696, 576460752303423488, 1152921504606846976, 65280, 129, 66, 36, 8, 16, 7926616819148718190],
-        whites_move: true,
-        points: board_representation::Points { white_points: 0, black_points: 0 },
-        points_delta: 0,
-        half_moves: 0,
-        half_move_clock: 0,
-        en_passant_target: None
-    };
+    let starting_board = board_representation::Board::from_fen(board_representation::STARTING_FEN).unwrap();
 
     let pieces_info = chess2::piece::constants::gen(); // Generate piece info
 
+    let book = chess2::book::Book::new(OPENING_BOOK).unwrap();
+
     let led_strobe_us: u32 = 200000; // Led strobe time (on time) in micro seconds
 
     let max_search_times: [u64; 8] = [1000, 3000, 5000, 10000, 20000, 30000, 50000, 100000]; // Options for maximum search times (ms) for the minimax algorithm
     let mut search_time_index: usize = 2; // Index for the currently selected minimax search time
     let max_search_depth = 6; // Maximum minimax search depth
+    let mut strength_index: usize = STRENGTH_LEVELS.len() - 1; // Index for the currently selected engine strength level, kept between games
+    let ponder_scan_ms = 50; // Time between checking the board and button while pondering
+    let clock_refresh_ms = 1000; // Time between updating the clock on the lcd while pondering
+    let clock_safety_ms = 500; // Time kept on the computers clock for showing its move, so it doesn't lose on time after the search
 
-    let mut opening_heatmap = [[0i16; 64]; 12];
+    // Transposition table is kept in a static so it doesn't use stack space
+    // The statics start uninitialised so they are zeroed at startup (.bss), rather than copying their initial values from flash (.data)
+    let transposition_table = cortex_m::singleton!(: MaybeUninit<TranspositionTable<EMBEDDED_ENTRIES>> = MaybeUninit::uninit()).unwrap().write(TranspositionTable::new());
+    let move_history = cortex_m::singleton!(: MaybeUninit<algorithm::MoveHistory> = MaybeUninit::uninit()).unwrap().write(algorithm::MoveHistory::new()); // Killer moves and history values for move ordering
+
+    let mut opening_heatmap: [[i16; 64]; 12];
     // Testing how many clock cycles it takes for the computer the generate a move from a starting board position at a search depth of 4
     /*
     cycle_counter.update();
@@ -126,9 +140,9 @@ fn main() -> ! {
         &chess2::embedded::ms_to_cycles(100000, clock_mhz as u64),
         4,
         0,
-        0,
         algorithm::AlphaBeta::new(),
         &opening_heatmap,
+        transposition_table,
         starting_board,
         &pieces_info,
     );
@@ -144,7 +158,7 @@ fn main() -> ! {
     loop {
         delay.delay_ms(1u16);
         lcd.clear(&mut delay);
-        opening_heatmap = [[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 10, 1, 18, 10, 9, 9, 1, 0, 1, 33, 61, 475, 338, 22, 6, 5, 51, 142, 1144, 2288, 2246, 392, 88, 80, 88, 74, 361, 111, 276, 124, 322, 62, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], [0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 1, 0, 4, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 35, 32, 94, 499, 3, 0], [1, 0, 0, 0, 0, 0, 0, 2, 0, 0, 2, 0, 0, 19, 0, 2, 0, 0, 15, 1, 2, 7, 0, 0, 1, 31, 0, 19, 145, 2, 79, 0, 9, 0, 11, 268, 58, 0, 1, 7, 16, 17, 1470, 1, 3, 2054, 9, 15, 0, 0, 2, 115, 62, 1, 0, 0, 0, 1, 0, 0, 5, 2, 2, 0], [1, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 3, 20, 22, 1, 0, 0, 1, 35, 0, 0, 17, 0, 2, 0, 314, 1, 13, 2, 0, 292, 0, 139, 2, 509, 2, 0, 47, 0, 35, 6, 108, 1, 162, 124, 1, 2, 3, 0, 51, 19, 57, 148, 1, 205, 0, 1, 0, 2, 0, 0, 3, 0, 0], [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 3, 2, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 1, 4, 1, 2, 0, 24, 22, 0, 13, 32, 3, 2, 24, 3, 0, 48, 7, 17, 6, 42, 0, 0, 0, 0, 66, 49, 67, 3, 0, 0, 0, 1, 0, 3, 3, 0, 0, 0], [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 9, 4, 1, 0, 0, 0, 23, 4, 0, 26, 498, 6], [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 348, 125, 418, 716, 867, 40, 525, 86, 17, 238, 834, 1360, 1326, 216, 134, 18, 0, 13, 174, 512, 190, 170, 68, 4, 1, 0, 34, 3, 4, 37, 4, 0, 0, 6, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0], [0, 8, 3, 3, 17, 458, 5, 0, 1, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0], [0, 13, 0, 2, 1, 1, 8, 0, 0, 4, 3, 219, 58, 2, 1, 0, 21, 32, 1057, 15, 1, 1874, 4, 29, 56, 0, 8, 130, 31, 3, 1, 10, 0, 9, 4, 40, 190, 2, 21, 0, 0, 0, 31, 0, 2, 1, 3, 0, 0, 0, 1, 2, 0, 2, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0], [0, 0, 0, 0, 1, 3, 0, 1, 1, 74, 0, 44, 307, 0, 387, 2, 20, 31, 2, 44, 56, 5, 9, 5, 27, 0, 241, 0, 2, 79, 3, 1, 0, 297, 3, 5, 2, 0, 98, 4, 0, 0, 60, 3, 1, 8, 0, 3, 0, 0, 0, 5, 1, 3, 1, 1, 0, 1, 0, 1, 0, 3, 0, 0], [1, 1, 2, 4, 5, 0, 0, 0, 0, 0, 36, 10, 62, 0, 0, 0, 0, 28, 0, 10, 2, 36, 6, 0, 79, 0, 0, 53, 5, 4, 12, 2, 0, 1, 1, 9, 3, 2, 0, 51, 1, 0, 1, 0, 0, 0, 0, 2, 0, 2, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0], [0, 0, 2, 7, 0, 4, 458, 0, 0, 0, 0, 0, 5, 17, 2, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]];
+        opening_heatmap = opening_heatmap::OPENING_HEATMAP;
 
         // Get player team
         let mut player_white = true;
@@ -181,6 +195,20 @@ fn main() -> ! {
 
         lcd.clear(&mut delay);
 
+        let mut clock = select_clock(&mut lcd, &mut delay, &mut button, &mut cycle_counter, clock_mhz as u64); // Chess clock for the game, None if the game is played without a clock
+
+        // Select the engine strength, starting from the strength of the last game
+        strength_index = select_option(&mut lcd, &mut delay, &mut button, &mut cycle_counter, "Engine strength", &STRENGTH_NAMES, strength_index);
+        let strength = STRENGTH_LEVELS[strength_index];
+        let search_depth = max_search_depth.min(strength.max_depth);
+
+        // The time taken to go through the menus seeds the randomness of weaker strength levels
+        let mut random = Random::new(cycle_counter.cycles);
+        let eval_noise = EvalNoise {
+            amount: strength.eval_noise,
+            seed: random.next_u64(),
+        };
+
         // Ensure the physical board is set up properly
         let mut physical_bitboard = embedded::read_board_halls(&mut grid_sr, &hall_sensor, &mut delay); // Get bitboard of pieces on the physical board
         {
@@ -204,8 +232,12 @@ fn main() -> ! {
             }
         }
 
-        // Initialise board
-        let mut board = starting_board;
+        // Initialise game state
+        let mut game = GameState::new(starting_board);
+        let mut pondered: Option<(board_representation::Board, algorithm::Move)> = None; // Board after the predicted player move, and the computers reply found while pondering
+        transposition_table.clear();
+        move_history.clear();
+        let result; // Result written to the PGN once the game has finished
 
         // Game loop
         // Each loop represents one turn
@@ -213,6 +245,8 @@ fn main() -> ! {
         'game: loop {
             lcd.clear(&mut delay);
 
+            let board = game.board; // Board before the turn has been made
+
             let players_turn = player_white == board.whites_move; // Determine wether the current turn is for the player or computer to make
 
             // Piece move for the chess engine and the physical board must be stored seperately
@@ -222,6 +256,12 @@ fn main() -> ! {
 
             physical_bitboard = embedded::read_board_halls(&mut grid_sr, &hall_sensor, &mut delay); // Get bitboard of pieces on the physical board before a piece has been moved
 
+            // Start the clock of the team to move
+            if let Some(clock) = &mut clock {
+                cycle_counter.update();
+                clock.start(board.whites_move, cycle_counter.cycles);
+            }
+
             // Get move from player / computer
             if players_turn {
 
@@ -229,6 +269,11 @@ fn main() -> ! {
 
                 // Loop until the player has made a proper move
                 let mut piece_removed = false;
+
+                // Predict the players move so the computer can search its reply while the player is thinking
+                let prediction = algorithm::predict_reply(&board, transposition_table, &pieces_info);
+                pondered = None;
+
                 loop {
                     lcd.set_cursor(&mut delay, [0, 0]);
                     lcd.print(&mut delay, "Players turn");
@@ -237,6 +282,74 @@ fn main() -> ! {
                     lcd_print_team(&mut lcd, &mut delay, player_white);
                     lcd.print(&mut delay, ")");
 
+                    // Show the players time, the game is lost once it runs out
+                    if let Some(clock) = &clock {
+                        lcd.print(&mut delay, " ");
+                        lcd_print_clock(&mut lcd, &mut delay, clock.remaining(player_white, cycle_counter.cycles), clock_mhz as u64);
+
+                        if clock.flagged(player_white, cycle_counter.cycles) {
+                            show_time_out(&mut lcd, &mut delay, &mut button, &mut cycle_counter, !player_white);
+                            result = GameResult::win(!player_white);
+                            break 'game;
+                        }
+                    }
+
+                    // Search the position after the predicted move until the player changes the board or the button
+                    // The search is restarted each loop while the board hasn't changed, continuing from the positions stored in the transposition table
+                    if let Some(ponder_board) = prediction {
+                        if pondered.is_none() && !piece_removed {
+                            move_history.new_search();
+                            cycle_counter.update();
+                            let start_cycles = cycle_counter.cycles;
+
+                            // With a clock the search is interrupted regularly so the time on the lcd keeps updating
+                            let stop_cycle;
+                            if clock.is_some() {
+                                stop_cycle = start_cycles + embedded::ms_to_cycles(clock_refresh_ms, clock_mhz as u64);
+                            } else {
+                                stop_cycle = u64::MAX;
+                            }
+
+                            let mut ponder_counter = embedded::ponder_counter::PonderCounter {
+                                counter: &mut cycle_counter,
+                                grid_sr: &mut grid_sr,
+                                hall_sensor: &hall_sensor,
+                                delay: &mut delay,
+                                button: &button,
+                                physical_bitboard: physical_bitboard,
+                                last_scan_cycle: 0, // Check the board on the first update in case the player has already started their move
+                                scan_cycles: chess2::embedded::ms_to_cycles(ponder_scan_ms, clock_mhz as u64),
+                                stop_cycle: stop_cycle,
+                                interrupted: false,
+                            };
+
+                            // Search without a time limit, the search only ends once it reaches search_depth or the player interrupts it
+                            let ponder_alpha_beta = algorithm::gen_best_move(
+                                true,
+                                &mut ponder_counter,
+                                &start_cycles,
+                                &TimeLimits::fixed(u64::MAX / 2),
+                                search_depth,
+                                0,
+                                algorithm::AlphaBeta::new(),
+                                &opening_heatmap,
+                                &eval_noise,
+                                transposition_table,
+                                move_history,
+                                &mut algorithm::SearchInfo::new(),
+                                ponder_board,
+                                &pieces_info,
+                            );
+
+                            // Only completed searches are kept, interrupted searches still fill the transposition table
+                            if !ponder_counter.interrupted {
+                                if let Some(piece_move) = ponder_alpha_beta.piece_move {
+                                    pondered = Some((ponder_board, piece_move));
+                                }
+                            }
+                        }
+                    }
+
                     let new_physical_bitboard = embedded::read_board_halls(&mut grid_sr, &hall_sensor, &mut delay); // Get bitboard of pieces on the physical board
 
                     let piece_change = chess2::find_piece_change(physical_bitboard, new_physical_bitboard);
@@ -300,6 +413,7 @@ fn main() -> ! {
 
                     // When the button is pressed greater than 9 times consecutevily resign
                     if button.consecutive_presses > 9 {
+                        result = GameResult::win(!player_white);
                         break 'game;
                     }
 
@@ -316,14 +430,14 @@ fn main() -> ! {
 
                         match player_move {
 
-                            // If the move was ok break the loop
+                            // If the move was ok break the loop, the players clock keeps running until the move is found to be legal
                             Ok(player_move) => {
                                 piece_physical_move = player_move;
                                 break;
                             },
 
                             // If there was an error with the move make the player revert the move so they can try again
-                            Err(pieces_moved) => {
+                            Err(_) => {
                                 lcd.clear(&mut delay);
                                 lcd.set_cursor(&mut delay, [0, 0]);
                                 lcd.print(&mut delay, "Invalid move");
@@ -345,24 +459,93 @@ fn main() -> ! {
                 lcd.print(&mut delay, "(");
                 lcd_print_team(&mut lcd, &mut delay, !player_white);
                 lcd.print(&mut delay, ")");
+                if let Some(clock) = &clock {
+                    lcd.print(&mut delay, " ");
+                    lcd_print_clock(&mut lcd, &mut delay, clock.remaining(!player_white, cycle_counter.cycles), clock_mhz as u64);
+                }
+
+                // Play a move from the opening book while the position is in the book
+                // Otherwise if the player made the predicted move use the reply that was found while pondering
+                let book_move = book.pick_move(&board, &mut random, &pieces_info);
+                let mut known_move = book_move;
+                if let Some((ponder_board, piece_move)) = pondered {
+                    if known_move.is_none() && ponder_board == board {
+                        known_move = Some(piece_move);
+                    }
+                }
 
+                // With a clock the time for the move is allocated from the computers remaining time
+                // Otherwise the move takes no longer than max_search_times[search_time_index]
+                // The search and choosing a weaker move share the time for the move
                 cycle_counter.update();
                 let start_cycles = cycle_counter.cycles;
+                let time_limits;
+                match &clock {
+                    Some(clock) => time_limits = TimeLimits::from_clock(
+                        clock.remaining(board.whites_move, start_cycles),
+                        clock.increment_cycles,
+                        embedded::ms_to_cycles(clock_safety_ms, clock_mhz as u64),
+                        board.half_moves,
+                    ),
+                    None => time_limits = TimeLimits::fixed(embedded::ms_to_cycles(max_search_times[search_time_index], clock_mhz as u64)),
+                }
+
+                match known_move {
+                    Some(piece_move) => piece_internal_move = piece_move,
+                    None => {
+                        move_history.new_search();
+                        let mut search_info = algorithm::SearchInfo::new();
+
+                        // Generate a move within the time limits, with a maximum search depth of search_depth
+                        let best_move = algorithm::gen_best_move(
+                            true,
+                            &mut cycle_counter,
+                            &start_cycles,
+                            &time_limits,
+                            search_depth,
+                            0,
+                            algorithm::AlphaBeta::new(),
+                            &opening_heatmap,
+                            &eval_noise,
+                            transposition_table,
+                            move_history,
+                            &mut search_info,
+                            board,
+                            &pieces_info,
+                        ).piece_move;
+
+                        print_search_info(&search_info, clock_mhz as u64);
+
+                        // The first iteration of the search always finishes, so there is only no move if the computer has no legal moves
+                        // The game is over before then, but the game is ended here instead of playing an invalid move
+                        match best_move {
+                            Some(piece_move) => piece_internal_move = piece_move,
+                            None => {
+                                result = GameResult::Unfinished;
+                                break 'game;
+                            },
+                        }
+                    },
+                }
+
+                // Weaker strength levels sometimes play a move that is worse than the best move
+                if book_move.is_none() && random.below(100) < strength.weaker_move_chance as u64 {
+                    if let Some(piece_move) = algorithm::weaker_move(strength.weaker_move_margin, search_depth, &mut random, &mut cycle_counter, &start_cycles, &time_limits, &opening_heatmap, &eval_noise, transposition_table, move_history, &board, &pieces_info) {
+                        piece_internal_move = piece_move;
+                    }
+                }
+
+                // The computers clock only counts the time it took to find the move
+                if let Some(clock) = &mut clock {
+                    cycle_counter.update();
+                    clock.stop(cycle_counter.cycles);
 
-                // Generate a move which takes no longer than max_search_times[search_time_index] and has a maximum search depth of max_search_depth
-                piece_internal_move = algorithm::gen_best_move(
-                    true,
-                    &mut cycle_counter,
-                    &start_cycles,
-                    &chess2::embedded::ms_to_cycles(max_search_times[search_time_index], clock_mhz as u64),
-                    max_search_depth,
-                    0,
-                    0,
-                    algorithm::AlphaBeta::new(),
-                    &opening_heatmap,
-                    board,
-                    &pieces_info,
-                ).piece_move.unwrap();
+                    if clock.flagged(!player_white, cycle_counter.cycles) {
+                        show_time_out(&mut lcd, &mut delay, &mut button, &mut cycle_counter, player_white);
+                        result = GameResult::win(player_white);
+                        break 'game;
+                    }
+                }
             }
 
             // Set piece_internal / piece_physical move (whichever hasn't been updated yet)
@@ -385,52 +568,62 @@ fn main() -> ! {
                 } 
             }
 
-            // Get friendly and enemy kings
-            let friendly_king_index;
-            let enemy_king_index;
-            if board.whites_move {
-                friendly_king_index = 5;
-                enemy_king_index = 11;
-            } else {
-                friendly_king_index = 11;
-                enemy_king_index = 5;
+            // Let the player select the piece to promote to when their pawn reaches the top row
+            // Illegal moves are checked first so they are rejected by new_turn without asking for a piece
+            if players_turn {
+                let pawn_index = piece_internal_move.initial_piece_coordinates.board_index;
+                let final_bit = piece_internal_move.final_piece_bit;
+                if ((pawn_index == 0 && final_bit < 8) || (pawn_index == 6 && final_bit > 55)) && chess2::board::move_generator::is_legal(&piece_internal_move, &board, &pieces_info) {
+                    piece_internal_move.promotion = Some(select_promotion(&mut lcd, &mut delay, &mut button, &mut cycle_counter) + pawn_index);
+                }
             }
 
-            let friendly_king = board_representation::BoardCoordinates {
-                board_index: friendly_king_index,
-                bit: chess2::find_bit_on(board.board[friendly_king_index], 0),
-            };
+            use chess2::board::move_generator::TurnError;
 
-            let enemy_king = board_representation::BoardCoordinates {
-                board_index: enemy_king_index,
-                bit: chess2::find_bit_on(board.board[enemy_king_index], 0),
-            };
+            // Make the turn, the game state board is updated if the move was valid
+            let new_turn_result = game.new_turn(&piece_internal_move, &pieces_info);
 
-            use chess2::board::move_generator;
-            use move_generator::TurnError;
+            // Stop the players clock once their move is legal, invalid moves are reverted on their time
+            if players_turn {
+                match new_turn_result {
+                    Err(TurnError::InvalidMove) | Err(TurnError::InvalidMoveCheck) => (),
+                    _ => {
+                        if let Some(clock) = &mut clock {
+                            cycle_counter.update();
+                            clock.stop(cycle_counter.cycles);
+                        }
+                    },
+                }
+            }
 
-            // Get new board after turn has been made
-            let team_bitboards = chess2::TeamBitboards::new(friendly_king.board_index, &board);
-            let enemy_attacks = move_generator::gen_enemy_attacks(&friendly_king, team_bitboards, &board, &pieces_info);
-            let new_turn_board = move_generator::new_turn(&piece_internal_move.initial_piece_coordinates, piece_internal_move.final_piece_bit, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, board, &pieces_info);
+            // Show computer move if it was made
+            if !players_turn && game.board != board {
 
-            match new_turn_board {
-                Ok(new_board) => {
+                // Get what the phsysical bitboard should be after the turn is made
+                let mut new_physical_bitboard = game.board.to_bitboard();
 
-                    // Get what the phsysical bitboard should be after the turn is made
-                    let mut new_physical_bitboard = new_board.to_bitboard();
+                if !player_white {
+                    new_physical_bitboard = chess2::flip_bitboard(new_physical_bitboard); // Flip the bitboard to physical board perspective
+                }
 
-                    if !player_white {
-                        new_physical_bitboard = chess2::flip_bitboard(new_physical_bitboard); // Flip the bitboard to physical board perspective
-                    }
+                // Tell the player which piece the computers pawn promotes to, otherwise show the move in standard algebraic notation
+                lcd.clear(&mut delay);
+                lcd.set_cursor(&mut delay, [0, 0]);
+                if let Some(promotion_index) = piece_internal_move.promotion {
+                    lcd.print(&mut delay, "Promote pawn to");
+                    lcd.set_cursor(&mut delay, [0, 1]);
+                    lcd.print(&mut delay, PIECE_NAMES[promotion_index % 6]);
+                } else {
+                    lcd.print(&mut delay, "Computer plays");
+                    lcd.set_cursor(&mut delay, [0, 1]);
+                    lcd.print(&mut delay, piece_internal_move.to_san(&board, &pieces_info).as_str());
+                }
 
-                    // Show computer move
-                    if !players_turn {
-                        show_move(new_physical_bitboard, &piece_physical_move, &mut grid_sr, &hall_sensor, led_strobe_us, &mut delay)
-                    }
+                show_move(new_physical_bitboard, &piece_physical_move, &mut grid_sr, &hall_sensor, led_strobe_us, &mut delay)
+            }
 
-                    board = new_board;
-                },
+            match new_turn_result {
+                Ok(()) => (),
                 Err(error) => {                    
                     match error {
                         TurnError::Win => {
@@ -461,6 +654,7 @@ fn main() -> ! {
                             lcd.set_cursor(&mut delay, [0, 1]);
                             lcd_print_team(&mut lcd, &mut delay, board.whites_move);
                             lcd.print(&mut delay, " team wins");
+                            result = GameResult::win(board.whites_move);
 
                             // Once the player presses the button end the game
                             loop {
@@ -474,6 +668,17 @@ fn main() -> ! {
                             lcd.set_cursor(&mut delay, [0, 0]);
                             lcd.print(&mut delay, "Game over (draw)");
 
+                            // Draws found by the game state still make the move, stalemates don't
+                            lcd.set_cursor(&mut delay, [0, 1]);
+                            if game.repetitions() >= 3 {
+                                lcd.print(&mut delay, "Repetition");
+                            } else if insufficient_material(&game.board) {
+                                lcd.print(&mut delay, "Dead position");
+                            } else {
+                                lcd.print(&mut delay, "Stalemate");
+                            }
+                            result = GameResult::Draw;
+
                             // Once the player presses the button end the game
                             loop {
                                 if button.press(&mut cycle_counter) {
@@ -509,26 +714,178 @@ fn main() -> ! {
                 },
             }
 
+            // Add the increment to the clock of the team that made the move
+            if let Some(clock) = &mut clock {
+                clock.increment(board.whites_move);
+            }
+
             // Draw game based on half move clock after the move has taken place
             // This is so checkmates made this move take priority over the half move draw
-            if board.half_move_clock >= 100 {
+            if game.board.half_move_clock >= 100 {
                 lcd.clear(&mut delay);
                 lcd.set_cursor(&mut delay, [0, 0]);
                 lcd.print(&mut delay, "Game over (draw)");
                 lcd.set_cursor(&mut delay, [0, 1]);
                 lcd.print(&mut delay, "Fifty move rule");
+                result = GameResult::Draw;
                 break 'game;
             }
 
             // Once the early and mid phases of the game are done reset the opening heatmap
             // After this point no heatmap will affect the computer moves
-            if board.half_moves > 20 {
+            if game.board.half_moves > 20 {
                 opening_heatmap = [[0i16; 64]; 12];
             }
 
             button.press(&mut cycle_counter);
         }
+
+        // Send the finished game over RTT so it can be saved on the computer
+        game.to_pgn(result, player_white, &pieces_info, &mut RttWriter).ok();
+    }
+}
+
+// Writes formatted text to the RTT output
+struct RttWriter;
+
+impl core::fmt::Write for RttWriter {
+    fn write_str(&mut self, text: &str) -> core::fmt::Result {
+        rprint!("{}", text);
+        Ok(())
+    }
+}
+
+// Polyglot opening book, any Polyglot book can be used if it fits in MAX_BOOK_BYTES of flash
+// The firmware without the book uses 59836 bytes of the 64K of flash (memory.x), leaving 5700 bytes for the book
+// The limit keeps about 580 bytes of that free for firmware changes, the linker also fails if the firmware and book don't fit
+// Measure the firmware again (llvm-size, minus the size of the book) before raising the limit
+const OPENING_BOOK: &[u8] = include_bytes!("../opening_book.bin");
+const MAX_BOOK_BYTES: usize = 5120; // 320 entries of 16 bytes
+const _: () = assert!(OPENING_BOOK.len() <= MAX_BOOK_BYTES, "Opening book is too large for the flash");
+
+// Names of the pieces in board index order
+const PIECE_NAMES: [&str; 6] = ["Pawn", "Rook", "Knight", "Bishop", "Queen", "King"];
+
+// Time controls that can be selected for a game, minutes and increment seconds
+// The options are shown after "No clock", and before "Custom" which lets the player choose the minutes and increment
+const TIME_CONTROLS: [(u64, u64); 3] = [(5, 0), (10, 5), (15, 10)];
+const TIME_CONTROL_NAMES: [&str; 5] = ["No clock", "5+0", "10+5", "15+10", "Custom"];
+
+// Options for custom time controls
+const CUSTOM_MINUTES: [u64; 8] = [1, 3, 5, 10, 15, 30, 60, 90];
+const CUSTOM_MINUTE_NAMES: [&str; 8] = ["1 min", "3 min", "5 min", "10 min", "15 min", "30 min", "60 min", "90 min"];
+const CUSTOM_INCREMENTS: [u64; 7] = [0, 1, 2, 3, 5, 10, 30];
+const CUSTOM_INCREMENT_NAMES: [&str; 7] = ["0 sec", "1 sec", "2 sec", "3 sec", "5 sec", "10 sec", "30 sec"];
+
+// Names of the engine strength levels, from weakest to strongest
+const STRENGTH_NAMES: [&str; STRENGTH_LEVELS.len()] = ["Beginner", "Novice", "Casual", "Club", "Strong", "Full strength"];
+
+// Opens a menu for the player to select one of the options, starting at option_index
+// Short press the button to cycle through the options, long press to select the option
+// Returns the index of the selected option
+fn select_option(lcd: &mut chess2::embedded::character_lcd::Lcd, delay: &mut Delay, button: &mut embedded::button::Button, cycle_counter: &mut embedded::cycle_counter::Counter, title: &str, options: &[&str], mut option_index: usize) -> usize {
+    lcd.clear(delay);
+
+    let mut increment_queued = false;
+    let mut press_start_cycle: Option<u64> = None;
+    loop {
+        lcd.set_cursor(delay, [0, 0]);
+        lcd.print(delay, title);
+        lcd.set_cursor(delay, [0, 1]);
+        lcd.print(delay, options[option_index]);
+
+        let button_pressed = button.press(cycle_counter);
+
+        // Long press the button to select the option
+        if button.long_press {
+            break;
+        }
+
+        // If the button is pressed increment the option_index
+        if button_pressed {
+            increment_queued = true;
+            press_start_cycle = button.press_start_cycle;
+        }
+
+        // Queue increment of the option index until after the button has been released
+        // This avoids the option changing while the user is trying to long press to select it
+        if increment_queued && press_start_cycle != button.press_start_cycle {
+            lcd.clear(delay);
+            option_index += 1;
+            if option_index > (options.len() - 1) {
+                option_index = 0;
+            }
+            increment_queued = false;
+        }
+    }
+    button.consecutive_presses = 0; // Reset consecutive presses that may have been made while the user cycles through the options
+    lcd.clear(delay);
+
+    option_index
+}
+
+// Opens a menu for the player to select the piece their pawn promotes to
+// Returns the board index of the piece relative to the teams pawn index
+fn select_promotion(lcd: &mut chess2::embedded::character_lcd::Lcd, delay: &mut Delay, button: &mut embedded::button::Button, cycle_counter: &mut embedded::cycle_counter::Counter) -> usize {
+    use chess2::board::move_generator::PROMOTION_INDEXES;
+
+    let mut piece_names = [""; PROMOTION_INDEXES.len()];
+    for i in 0..PROMOTION_INDEXES.len() {
+        piece_names[i] = PIECE_NAMES[PROMOTION_INDEXES[i]];
+    }
+
+    PROMOTION_INDEXES[select_option(lcd, delay, button, cycle_counter, "Promote pawn to", &piece_names, 0)]
+}
+
+// Opens menus for the player to select the time control of the game
+// Returns None if the game is played without a clock
+fn select_clock(lcd: &mut chess2::embedded::character_lcd::Lcd, delay: &mut Delay, button: &mut embedded::button::Button, cycle_counter: &mut embedded::cycle_counter::Counter, clock_mhz: u64) -> Option<ChessClock> {
+    let time_control_index = select_option(lcd, delay, button, cycle_counter, "Chess clock", &TIME_CONTROL_NAMES, 0);
+
+    let minutes;
+    let increment_seconds;
+    if time_control_index == 0 {
+        return None;
+    } else if time_control_index <= TIME_CONTROLS.len() {
+        (minutes, increment_seconds) = TIME_CONTROLS[time_control_index - 1];
+    } else {
+        minutes = CUSTOM_MINUTES[select_option(lcd, delay, button, cycle_counter, "Clock minutes", &CUSTOM_MINUTE_NAMES, 0)];
+        increment_seconds = CUSTOM_INCREMENTS[select_option(lcd, delay, button, cycle_counter, "Clock increment", &CUSTOM_INCREMENT_NAMES, 0)];
+    }
+
+    Some(ChessClock::new(embedded::ms_to_cycles(minutes * 60000, clock_mhz), embedded::ms_to_cycles(increment_seconds * 1000, clock_mhz)))
+}
+
+// Shows that a team lost on time, and waits for the player to press the button
+fn show_time_out(lcd: &mut chess2::embedded::character_lcd::Lcd, delay: &mut Delay, button: &mut embedded::button::Button, cycle_counter: &mut embedded::cycle_counter::Counter, winner_white: bool) {
+    lcd.clear(delay);
+    lcd.set_cursor(delay, [0, 0]);
+    lcd.print(delay, "Game over (time)");
+    lcd.set_cursor(delay, [0, 1]);
+    lcd_print_team(lcd, delay, winner_white);
+    lcd.print(delay, " team wins");
+
+    while !button.press(cycle_counter) {}
+}
+
+// Prints the time left on a clock to the lcd as minutes:seconds, rounded up so the clock only shows 0:00 once the time has run out
+fn lcd_print_clock(lcd: &mut chess2::embedded::character_lcd::Lcd, delay: &mut Delay, cycles: u64, clock_mhz: u64) {
+    let second_cycles = embedded::ms_to_cycles(1000, clock_mhz);
+    let seconds = cycles.div_ceil(second_cycles);
+
+    let af = arrform!(16, "{:>2}:{:02}", seconds / 60, seconds % 60);
+    lcd.print(delay, af.as_str());
+}
+
+// Logs the results of a search over rtt
+fn print_search_info(search_info: &algorithm::SearchInfo, clock_mhz: u64) {
+    rprintln!("Depth {} nodes {} cutoffs {} score {} time {}ms", search_info.depth, search_info.nodes, search_info.cutoffs, search_info.score, search_info.elapsed_cycles / (clock_mhz * 1000));
+
+    rprint!("PV");
+    for piece_move in search_info.pv() {
+        rprint!(" {}", piece_move.to_uci().as_str());
     }
+    rprintln!("");
 }
 
 // Prints team (white / black) to lcd
@@ -0,0 +1,354 @@
+use core::fmt::Write;
+
+use arrform::ArrForm;
+
+use crate::algorithm::Move;
+use crate::board::board_representation;
+
+// Longest move in standard algebraic notation, e.g. "Qa1xb2+" or "exd8=Q#"
+pub const SAN_MAX_LEN: usize = 8;
+
+// Longest move in UCI long algebraic notation, e.g. "e7e8q"
+pub const UCI_MAX_LEN: usize = 5;
+
+// Errors that can be encountered when parsing a move
+#[derive(PartialEq, Debug)]
+pub enum MoveParseError {
+    Empty, // The move has no characters
+    InvalidCharacter(char), // The move contains a character that isn't part of the notation
+    InvalidSquare, // The move doesn't contain a valid square
+    NoLegalMove, // No legal move matches the move
+    Ambiguous, // More than one legal move matches the move
+}
+
+// Piece letters used by SAN, in board index order (the pawn has no letter)
+const SAN_PIECES: [char; 6] = [' ', 'R', 'N', 'B', 'Q', 'K'];
+
+// Returns the only legal move that matches, or an error if no move or more than one move matches
+fn find_move(board: &board_representation::Board, pieces_info: &[crate::piece::constants::PieceInfo; 12], matches: impl Fn(&Move) -> bool) -> Result<Move, MoveParseError> {
+    use crate::board::move_generator;
+
+    let moves = move_generator::legal_moves(board, pieces_info);
+
+    let mut found_move = None;
+    for i in 0..moves.len {
+        if matches(&moves.moves[i]) {
+            if found_move.is_some() {
+                return Err(MoveParseError::Ambiguous);
+            }
+            found_move = Some(moves.moves[i]);
+        }
+    }
+
+    found_move.ok_or(MoveParseError::NoLegalMove)
+}
+
+// Finds the legal move for a move in standard algebraic notation (e.g. "e4", "Nbd7", "exd8=Q+", "O-O")
+// Check, checkmate and annotation suffixes are ignored
+pub fn parse_san(san: &str, board: &board_representation::Board, pieces_info: &[crate::piece::constants::PieceInfo; 12]) -> Result<Move, MoveParseError> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+    if san.is_empty() {
+        return Err(MoveParseError::Empty);
+    }
+
+    // The notation only uses ascii characters, so the move can be read a byte at a time
+    if let Some(c) = san.chars().find(|c| !c.is_ascii()) {
+        return Err(MoveParseError::InvalidCharacter(c));
+    }
+
+    // Board index of the pawn of the team to move, the board indexes of the other pieces are relative to it
+    let team_offset = if board.whites_move { 0 } else { 6 };
+
+    // Castling moves the king two squares towards the rook
+    if san == "O-O" || san == "0-0" || san == "O-O-O" || san == "0-0-0" {
+        let (king, _) = board.kings();
+        let final_bit = if san.len() == 3 { king.bit + 2 } else { king.bit.wrapping_sub(2) };
+        return find_move(board, pieces_info, |piece_move| piece_move.initial_piece_coordinates == king && piece_move.final_piece_bit == final_bit);
+    }
+
+    let chars = san.as_bytes();
+
+    // Piece moves start with the piece letter, pawn moves don't
+    let mut start = 0;
+    let mut piece_index = 0;
+    for (i, &piece) in SAN_PIECES.iter().enumerate().skip(1) {
+        if chars[0] as char == piece {
+            piece_index = i;
+            start = 1;
+        }
+    }
+
+    // Pawn moves can end with the promotion piece, with or without an '='
+    let mut end = chars.len();
+    let mut promotion = None;
+    if piece_index == 0 {
+        for (i, &piece) in SAN_PIECES.iter().enumerate().take(5).skip(1) {
+            if chars[end - 1] as char == piece {
+                promotion = Some(i + team_offset);
+                end -= 1;
+                if end > 0 && chars[end - 1] == b'=' {
+                    end -= 1;
+                }
+                break;
+            }
+        }
+    }
+
+    if end < start + 2 {
+        return Err(MoveParseError::InvalidSquare);
+    }
+    let final_bit = crate::ccn_to_bit(&san[end - 2..end]).ok_or(MoveParseError::InvalidSquare)?;
+
+    // The file and / or rank of the initial square can be given when more than one piece can move to the final square
+    let mut file = None;
+    let mut row = None;
+    for c in san[start..end - 2].chars() {
+        match c {
+            'a'..='h' => file = Some(c as usize - 'a' as usize),
+            '1'..='8' => row = Some(7 - (c as usize - '1' as usize)),
+            'x' => (),
+            _ => return Err(MoveParseError::InvalidCharacter(c)),
+        }
+    }
+
+    find_move(board, pieces_info, |piece_move| {
+        let initial_bit = piece_move.initial_piece_coordinates.bit;
+        piece_move.initial_piece_coordinates.board_index == piece_index + team_offset
+            && piece_move.final_piece_bit == final_bit
+            && piece_move.promotion == promotion
+            && file.is_none_or(|file| initial_bit % 8 == file)
+            && row.is_none_or(|row| initial_bit / 8 == row)
+    })
+}
+
+// Formats a legal move in standard algebraic notation
+// The file and / or rank of the initial square are added when another piece of the same type can move to the final square
+pub fn to_san(piece_move: &Move, board: &board_representation::Board, pieces_info: &[crate::piece::constants::PieceInfo; 12]) -> ArrForm<SAN_MAX_LEN> {
+    use crate::board::move_generator;
+    use crate::board::move_generator::TurnError;
+
+    let mut san = ArrForm::<SAN_MAX_LEN>::new();
+
+    // The buffer is large enough for any move, so writes can't fail
+    let board_index = piece_move.initial_piece_coordinates.board_index;
+    let initial_bit = piece_move.initial_piece_coordinates.bit;
+    let final_bit = piece_move.final_piece_bit;
+    let piece_index = board_index % 6;
+
+    if piece_index == 5 && (final_bit == initial_bit + 2 || final_bit + 2 == initial_bit) {
+        if final_bit > initial_bit {
+            san.write_str("O-O").ok();
+        } else {
+            san.write_str("O-O-O").ok();
+        }
+    } else {
+        // Pawns capture diagonally, including en passant captures where the final square is empty
+        let capture = crate::bit_on(board.to_bitboard(), final_bit) || (piece_index == 0 && initial_bit % 8 != final_bit % 8);
+
+        if piece_index == 0 {
+            if capture {
+                san.write_str(&crate::bit_to_ccn(initial_bit)[..1]).ok();
+            }
+        } else {
+            san.write_char(SAN_PIECES[piece_index]).ok();
+
+            // Find the other pieces that can move to the final square
+            let moves = move_generator::legal_moves(board, pieces_info);
+            let mut ambiguous = false;
+            let mut same_file = false;
+            let mut same_row = false;
+            for i in 0..moves.len {
+                let other_bit = moves.moves[i].initial_piece_coordinates.bit;
+                if moves.moves[i].initial_piece_coordinates.board_index == board_index && moves.moves[i].final_piece_bit == final_bit && other_bit != initial_bit {
+                    ambiguous = true;
+                    same_file |= other_bit % 8 == initial_bit % 8;
+                    same_row |= other_bit / 8 == initial_bit / 8;
+                }
+            }
+
+            let ccn = crate::bit_to_ccn(initial_bit);
+            if ambiguous && !same_file {
+                san.write_str(&ccn[..1]).ok();
+            } else if ambiguous && !same_row {
+                san.write_str(&ccn[1..]).ok();
+            } else if ambiguous {
+                san.write_str(ccn).ok();
+            }
+        }
+
+        if capture {
+            san.write_char('x').ok();
+        }
+        san.write_str(crate::bit_to_ccn(final_bit)).ok();
+
+        if let Some(promotion_index) = piece_move.promotion {
+            san.write_char('=').ok();
+            san.write_char(SAN_PIECES[promotion_index % 6]).ok();
+        }
+    }
+
+    // Check and checkmate suffixes
+    let (friendly_king, enemy_king) = board.kings();
+    let team_bitboards = crate::TeamBitboards::new(friendly_king.board_index, board);
+    let enemy_attacks = move_generator::gen_enemy_attacks(&friendly_king, team_bitboards, board, pieces_info);
+    match move_generator::new_turn(&piece_move.initial_piece_coordinates, final_bit, piece_move.promotion, friendly_king, &enemy_king, &enemy_attacks, team_bitboards, *board, pieces_info) {
+        Ok(new_board) => {
+            if new_board.in_check() {
+                san.write_char('+').ok();
+            }
+        },
+        Err(TurnError::Win) => {
+            san.write_char('#').ok();
+        },
+        Err(_) => (),
+    }
+
+    san
+}
+
+// Finds the legal move for a move in UCI long algebraic notation (e.g. "e2e4", "e7e8q", castling is "e1g1")
+pub fn parse_uci(uci: &str, board: &board_representation::Board, pieces_info: &[crate::piece::constants::PieceInfo; 12]) -> Result<Move, MoveParseError> {
+    if uci.is_empty() {
+        return Err(MoveParseError::Empty);
+    }
+    if uci.len() < 4 || !uci.is_char_boundary(2) || !uci.is_char_boundary(4) {
+        return Err(MoveParseError::InvalidSquare);
+    }
+    let initial_bit = crate::ccn_to_bit(&uci[0..2]).ok_or(MoveParseError::InvalidSquare)?;
+    let final_bit = crate::ccn_to_bit(&uci[2..4]).ok_or(MoveParseError::InvalidSquare)?;
+
+    // The promotion piece is a lower case letter after the squares
+    let mut promotion = None;
+    let mut chars = uci[4..].chars();
+    if let Some(c) = chars.next() {
+        let mut promotion_index = None;
+        for (i, piece) in SAN_PIECES.iter().enumerate().take(5).skip(1) {
+            if c == piece.to_ascii_lowercase() {
+                promotion_index = Some(i);
+            }
+        }
+        match promotion_index {
+            Some(i) if board.whites_move => promotion = Some(i),
+            Some(i) => promotion = Some(i + 6),
+            None => return Err(MoveParseError::InvalidCharacter(c)),
+        }
+    }
+    if let Some(c) = chars.next() {
+        return Err(MoveParseError::InvalidCharacter(c));
+    }
+
+    find_move(board, pieces_info, |piece_move| piece_move.initial_piece_coordinates.bit == initial_bit && piece_move.final_piece_bit == final_bit && piece_move.promotion == promotion)
+}
+
+// Formats a move in UCI long algebraic notation, the initial and final squares followed by the promotion piece
+// Unlike standard algebraic notation the board isn't needed, castling is the king moving two squares
+pub fn to_uci(piece_move: &Move) -> ArrForm<UCI_MAX_LEN> {
+    let mut uci = ArrForm::<UCI_MAX_LEN>::new();
+
+    // The buffer is large enough for any move, so writes can't fail
+    uci.write_str(crate::bit_to_ccn(piece_move.initial_piece_coordinates.bit)).ok();
+    uci.write_str(crate::bit_to_ccn(piece_move.final_piece_bit)).ok();
+    if let Some(promotion_index) = piece_move.promotion {
+        uci.write_char(SAN_PIECES[promotion_index % 6].to_ascii_lowercase()).ok();
+    }
+
+    uci
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::board_representation::{Board, STARTING_FEN};
+
+    fn san_move(san: &str, fen: &str) -> Result<(&'static str, &'static str, Option<usize>), MoveParseError> {
+        let pieces_info = crate::piece::constants::gen();
+        let piece_move = parse_san(san, &Board::from_fen(fen).unwrap(), &pieces_info)?;
+        Ok((crate::bit_to_ccn(piece_move.initial_piece_coordinates.bit), crate::bit_to_ccn(piece_move.final_piece_bit), piece_move.promotion))
+    }
+
+    #[test]
+    fn parses_san_moves() {
+        assert_eq!(san_move("e4", STARTING_FEN), Ok(("e2", "e4", None)));
+        assert_eq!(san_move("Nf3", STARTING_FEN), Ok(("g1", "f3", None)));
+        assert_eq!(san_move("Nc6", "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"), Ok(("b8", "c6", None)));
+
+        // Captures, disambiguation and promotions
+        let fen = "1n2k3/P7/8/3p4/4P3/8/8/R3K2R w KQ - 0 1";
+        assert_eq!(san_move("exd5", fen), Ok(("e4", "d5", None)));
+        assert_eq!(san_move("Rad1", "4k3/8/8/8/8/8/4K3/R6R w - - 0 1"), Ok(("a1", "d1", None)));
+        assert_eq!(san_move("Rhd1", "4k3/8/8/8/8/8/4K3/R6R w - - 0 1"), Ok(("h1", "d1", None)));
+        assert_eq!(san_move("Rd1", "4k3/8/8/8/8/8/4K3/R6R w - - 0 1"), Err(MoveParseError::Ambiguous));
+        assert_eq!(san_move("axb8=N+", fen), Ok(("a7", "b8", Some(2))));
+        assert_eq!(san_move("a8Q", fen), Ok(("a7", "a8", Some(4))));
+        assert_eq!(san_move("O-O-O", fen), Ok(("e1", "c1", None)));
+        assert_eq!(san_move("O-O!", fen), Ok(("e1", "g1", None)));
+
+        assert_eq!(san_move("Ke3", fen), Err(MoveParseError::NoLegalMove));
+        assert_eq!(san_move("Nz3", fen), Err(MoveParseError::InvalidSquare));
+        assert_eq!(san_move("R-d1", fen), Err(MoveParseError::InvalidCharacter('-')));
+        assert_eq!(san_move("+", fen), Err(MoveParseError::Empty));
+        assert_eq!(san_move("=R", fen), Err(MoveParseError::InvalidSquare));
+        assert_eq!(san_move("é4", fen), Err(MoveParseError::InvalidCharacter('é')));
+    }
+
+    fn formatted_san(from: &str, to: &str, promotion: Option<usize>, fen: &str) -> String {
+        let pieces_info = crate::piece::constants::gen();
+        let board = Board::from_fen(fen).unwrap();
+        let moves = crate::board::move_generator::legal_moves(&board, &pieces_info);
+        let piece_move = moves.as_slice().iter().find(|piece_move| {
+            crate::bit_to_ccn(piece_move.initial_piece_coordinates.bit) == from && crate::bit_to_ccn(piece_move.final_piece_bit) == to && piece_move.promotion == promotion
+        }).unwrap();
+
+        let san = to_san(piece_move, &board, &pieces_info);
+        assert_eq!(parse_san(san.as_str(), &board, &pieces_info).unwrap(), *piece_move);
+        String::from(san.as_str())
+    }
+
+    #[test]
+    fn formats_san_moves() {
+        assert_eq!(formatted_san("e2", "e4", None, STARTING_FEN), "e4");
+        assert_eq!(formatted_san("g1", "f3", None, STARTING_FEN), "Nf3");
+
+        let fen = "1n2k3/P7/8/3p4/4P3/8/8/R3K2R w KQ - 0 1";
+        assert_eq!(formatted_san("e4", "d5", None, fen), "exd5");
+        assert_eq!(formatted_san("a7", "b8", Some(2), fen), "axb8=N");
+        assert_eq!(formatted_san("a7", "a8", Some(4), fen), "a8=Q");
+        assert_eq!(formatted_san("e1", "c1", None, fen), "O-O-O");
+        assert_eq!(formatted_san("e1", "g1", None, fen), "O-O");
+        assert_eq!(formatted_san("a1", "a6", None, "4k3/8/8/8/8/8/4K3/R6R w - - 0 1"), "Ra6");
+        assert_eq!(formatted_san("a1", "d1", None, "4k3/8/8/8/8/8/4K3/R6R w - - 0 1"), "Rad1");
+        assert_eq!(formatted_san("a1", "a3", None, "4k3/8/8/R7/8/8/4K3/R7 w - - 0 1"), "R1a3");
+        assert_eq!(formatted_san("b2", "d4", None, "4k3/8/1Q6/8/8/8/1Q1Q1K2/8 w - - 0 1"), "Qb2d4");
+
+        // En passant, check and checkmate
+        assert_eq!(formatted_san("a1", "a8", None, "4k3/8/8/8/8/8/8/R3K3 w - - 0 1"), "Ra8+");
+        assert_eq!(formatted_san("e5", "d6", None, "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1"), "exd6");
+        assert_eq!(formatted_san("a1", "a8", None, "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"), "Ra8#");
+    }
+
+    #[test]
+    fn uci_moves_round_trip() {
+        let pieces_info = crate::piece::constants::gen();
+
+        let cases = [
+            ("e2e4", STARTING_FEN),
+            ("e1g1", "4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1"),
+            ("e8c8", "r3k3/8/8/8/8/8/8/4K3 b q - 0 1"),
+            ("a7a8q", "4k3/P7/8/8/8/8/8/4K3 w - - 0 1"),
+            ("h2h1n", "4k3/8/8/8/8/8/7p/K7 b - - 0 1"),
+        ];
+        for (uci, fen) in cases {
+            let board = Board::from_fen(fen).unwrap();
+            let piece_move = parse_uci(uci, &board, &pieces_info).unwrap();
+            assert_eq!(to_uci(&piece_move).as_str(), uci);
+        }
+
+        let board = Board::from_fen("4k3/7P/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(parse_uci("h7h8r", &board, &pieces_info).unwrap().promotion, Some(1));
+        assert_eq!(parse_uci("h7h8", &board, &pieces_info), Err(MoveParseError::NoLegalMove));
+        assert_eq!(parse_uci("h7h8x", &board, &pieces_info), Err(MoveParseError::InvalidCharacter('x')));
+        assert_eq!(parse_uci("h7h8qq", &board, &pieces_info), Err(MoveParseError::InvalidCharacter('q')));
+        assert_eq!(parse_uci("h7i8", &board, &pieces_info), Err(MoveParseError::InvalidSquare));
+        assert_eq!(parse_uci("", &board, &pieces_info), Err(MoveParseError::Empty));
+    }
+}
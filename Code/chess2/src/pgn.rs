@@ -0,0 +1,159 @@
+// Parts of a PGN file that are needed to replay its games
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Token<'a> {
+    Tag(&'a str, &'a str), // Name and value of a tag pair, e.g. [Event "Club match"]
+    Move(&'a str), // Move in standard algebraic notation
+    Result(&'a str), // Result at the end of a game ("1-0", "0-1", "1/2-1/2" or "*")
+}
+
+// Result of a game, as written at the end of the PGN
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Unfinished,
+}
+
+impl GameResult {
+    // Returns the result for a win by the white or black team
+    pub fn win(white: bool) -> Self {
+        if white {
+            GameResult::WhiteWins
+        } else {
+            GameResult::BlackWins
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unfinished => "*",
+        }
+    }
+}
+
+// Iterator over the tokens of a PGN file, which can contain more than one game
+// Comments, variations, numeric annotation glyphs and move numbers are skipped
+pub struct Tokens<'a> {
+    text: &'a str,
+    index: usize, // Index of the next character to read
+}
+
+impl<'a> Tokens<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Tokens { text, index: 0 }
+    }
+
+    // Moves the index past the next occurrence of the character, or to the end of the text
+    fn skip_past(&mut self, end: u8) {
+        let bytes = self.text.as_bytes();
+        while self.index < bytes.len() && bytes[self.index] != end {
+            self.index += 1;
+        }
+        self.index += 1;
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let bytes = self.text.as_bytes();
+
+        loop {
+            while self.index < bytes.len() && bytes[self.index].is_ascii_whitespace() {
+                self.index += 1;
+            }
+            if self.index >= bytes.len() {
+                return None;
+            }
+
+            let start = self.index;
+            match bytes[start] {
+                // Tag pair, the value is the text between the quotes
+                b'[' => {
+                    self.skip_past(b']');
+                    let tag = &self.text[start + 1..(self.index - 1).min(bytes.len())];
+                    let name = tag.split_ascii_whitespace().next().unwrap_or("");
+                    let value = tag.split('"').nth(1).unwrap_or("");
+                    return Some(Token::Tag(name, value));
+                },
+                b'{' => self.skip_past(b'}'),
+                b';' | b'%' => self.skip_past(b'\n'),
+
+                // Variations can contain other variations
+                b'(' => {
+                    let mut depth = 0;
+                    while self.index < bytes.len() {
+                        match bytes[self.index] {
+                            b'(' => depth += 1,
+                            b')' => depth -= 1,
+                            b'{' => {
+                                self.skip_past(b'}');
+                                continue;
+                            },
+                            _ => (),
+                        }
+                        self.index += 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                },
+                _ => {
+                    while self.index < bytes.len() && !bytes[self.index].is_ascii_whitespace() && !b"[]{}();".contains(&bytes[self.index]) {
+                        self.index += 1;
+                    }
+                    let token = &self.text[start..self.index];
+
+                    if token == "1-0" || token == "0-1" || token == "1/2-1/2" || token == "*" {
+                        return Some(Token::Result(token));
+                    }
+
+                    // Numeric annotation glyph
+                    if token.starts_with('$') {
+                        continue;
+                    }
+
+                    // Move numbers can be followed by the move without a space (e.g. "1.e4"), castling can start with a zero
+                    let san = if token.starts_with("0-0") {
+                        token
+                    } else {
+                        token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.')
+                    };
+                    if !san.is_empty() {
+                        return Some(Token::Move(san));
+                    }
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenizes_games() {
+        let pgn = "[Event \"Club match\"]\n[White \"A\"]\n\n1. e4 {best by test} e5 2.Nf3 $1 (2. f4 exf4 (2... d5)) Nc6; comment\n3... a6?! 0-0 1/2-1/2\n\n[Event \"Second\"]\n1. d4 *";
+        let tokens: Vec<Token> = Tokens::new(pgn).collect();
+        assert_eq!(tokens, [
+            Token::Tag("Event", "Club match"),
+            Token::Tag("White", "A"),
+            Token::Move("e4"),
+            Token::Move("e5"),
+            Token::Move("Nf3"),
+            Token::Move("Nc6"),
+            Token::Move("a6?!"),
+            Token::Move("0-0"),
+            Token::Result("1/2-1/2"),
+            Token::Tag("Event", "Second"),
+            Token::Move("d4"),
+            Token::Result("*"),
+        ]);
+    }
+}
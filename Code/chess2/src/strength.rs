@@ -0,0 +1,90 @@
+// Settings that weaken the engine for players that can't beat it at full strength
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Strength {
+    pub max_depth: usize, // Maximum search depth
+    pub eval_noise: i16, // Maximum centipawns added to or subtracted from each evaluation
+    pub weaker_move_chance: u8, // Percentage of moves where a weaker move can be played instead of the best move
+    pub weaker_move_margin: i16, // Maximum centipawns a weaker move can score below the best move
+}
+
+// Strength levels from weakest to strongest, the last level is the full strength engine
+pub const STRENGTH_LEVELS: [Strength; 6] = [
+    Strength { max_depth: 1, eval_noise: 150, weaker_move_chance: 40, weaker_move_margin: 300 },
+    Strength { max_depth: 2, eval_noise: 100, weaker_move_chance: 30, weaker_move_margin: 200 },
+    Strength { max_depth: 3, eval_noise: 60, weaker_move_chance: 20, weaker_move_margin: 120 },
+    Strength { max_depth: 4, eval_noise: 30, weaker_move_chance: 10, weaker_move_margin: 60 },
+    Strength { max_depth: 5, eval_noise: 10, weaker_move_chance: 0, weaker_move_margin: 0 },
+    Strength { max_depth: usize::MAX, eval_noise: 0, weaker_move_chance: 0, weaker_move_margin: 0 },
+];
+
+// Noise added to evaluations by the search
+// The noise only depends on the position and seed, so a position gets the same evaluation each time it is searched and transposition table scores stay consistent
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct EvalNoise {
+    pub amount: i16, // Maximum centipawns added to or subtracted from an evaluation
+    pub seed: u64, // Changes the noise of each position, e.g. for each game
+}
+
+impl EvalNoise {
+    pub const NONE: EvalNoise = EvalNoise { amount: 0, seed: 0 };
+
+    // Returns the noise for the position with the given zobrist key
+    pub fn value(&self, key: u64) -> i16 {
+        if self.amount <= 0 {
+            return 0;
+        }
+
+        let hash = (key ^ self.seed).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        ((hash >> 32) % (2 * self.amount as u64 + 1)) as i16 - self.amount
+    }
+}
+
+// Xorshift pseudo random number generator
+// There is no source of randomness on the chess board, so it is seeded from the time the player takes to start a game
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        // The state can't be zero, since zero only generates zeros
+        let state = if seed == 0 { 0x2545_F491_4F6C_DD1D } else { seed };
+
+        Random { state }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    // Returns a number from 0 to max - 1
+    pub fn below(&mut self, max: u64) -> u64 {
+        self.next_u64() % max
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eval_noise_is_bounded_and_repeatable() {
+        let noise = EvalNoise { amount: 20, seed: 12345 };
+
+        let mut random = Random::new(0);
+        let mut noisy = false;
+        for _ in 0..1000 {
+            let key = random.next_u64();
+            let value = noise.value(key);
+            assert!((-20..=20).contains(&value));
+            assert_eq!(noise.value(key), value);
+            assert_eq!(EvalNoise::NONE.value(key), 0);
+            noisy |= value != 0;
+        }
+        assert!(noisy);
+    }
+}
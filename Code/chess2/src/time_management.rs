@@ -0,0 +1,202 @@
+use crate::algorithm::SearchInfo;
+
+// Number of moves the remaining time is shared between at the start of the game
+// Later in the game fewer moves are expected to be left, down to MIN_MOVES_TO_GO
+const MAX_MOVES_TO_GO: u64 = 50;
+const MIN_MOVES_TO_GO: u64 = 20;
+
+// The hard limit can be this many times longer than the soft limit, so an iteration that was started can still finish
+const HARD_LIMIT_FACTOR: u64 = 4;
+
+// A move never uses more than a 1 / MAX_TIME_FRACTION of the remaining time
+const MAX_TIME_FRACTION: u64 = 3;
+
+// Number of iterations with the same best move before the search can stop at half of the soft limit
+const STABLE_ITERATIONS: usize = 3;
+
+// Score drop (centipawns) since the last iteration that lets the search continue up to twice the soft limit
+const SCORE_DROP: i16 = 30;
+
+// Time allowed for a search, in cycles from the start of the search
+// No new iteration is started after the soft limit, the search stops in the middle of an iteration at the hard limit
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TimeLimits {
+    pub soft_cycles: u64,
+    pub hard_cycles: u64,
+}
+
+impl TimeLimits {
+    // Limits for a fixed search time, there is no soft limit so the search uses all of the time
+    pub fn fixed(cycles: u64) -> Self {
+        TimeLimits {
+            soft_cycles: u64::MAX,
+            hard_cycles: cycles,
+        }
+    }
+
+    // Limits for a move from a game clock
+    // remaining_cycles is the time left on the clock and increment_cycles is the time added after each move
+    // safety_cycles is kept on the clock to cover the time spent outside of the search (e.g. reading the move from the board)
+    pub fn from_clock(remaining_cycles: u64, increment_cycles: u64, safety_cycles: u64, half_moves: i16) -> Self {
+        let available_cycles = remaining_cycles.saturating_sub(safety_cycles);
+
+        // Share the remaining time between the moves left in the game, and use most of the increment since it is added back after the move
+        let moves_to_go = MAX_MOVES_TO_GO.saturating_sub(half_moves.max(0) as u64 / 2).max(MIN_MOVES_TO_GO);
+        let soft_cycles = available_cycles / moves_to_go + increment_cycles * 3 / 4;
+
+        let hard_cycles = (soft_cycles * HARD_LIMIT_FACTOR).min(available_cycles / MAX_TIME_FRACTION);
+
+        TimeLimits {
+            soft_cycles: soft_cycles.min(hard_cycles),
+            hard_cycles,
+        }
+    }
+
+    // Returns the cycles after which no new iteration should be started
+    // Stops early if the best move hasn't changed for several iterations, and uses more time if the score is dropping
+    pub fn iteration_cycles(&self, search_info: &SearchInfo) -> u64 {
+        let cycles;
+        if search_info.score_drop >= SCORE_DROP {
+            cycles = self.soft_cycles.saturating_mul(2);
+        } else if search_info.stable_iterations >= STABLE_ITERATIONS {
+            cycles = self.soft_cycles / 2;
+        } else {
+            cycles = self.soft_cycles;
+        }
+        cycles.min(self.hard_cycles)
+    }
+}
+
+// Game clock for both teams, times are in cycles
+// Only the clock of the team to move runs, it is started at the start of a turn and stopped once the move is made
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ChessClock {
+    pub remaining_cycles: [u64; 2], // Time left for the white and black team, not counting the time of the running clock
+    pub increment_cycles: u64, // Time added to a team's clock after each of their moves
+    pub running: Option<(bool, u64)>, // Team of the running clock (true for white) and the cycle it was started at
+}
+
+impl ChessClock {
+    pub fn new(initial_cycles: u64, increment_cycles: u64) -> Self {
+        ChessClock {
+            remaining_cycles: [initial_cycles; 2],
+            increment_cycles,
+            running: None,
+        }
+    }
+
+    // Starts the clock of a team, stopping the clock of the other team if it is running
+    // Starting a clock that is already running keeps its start, so a turn that restarts keeps counting its time
+    pub fn start(&mut self, white: bool, cycles: u64) {
+        match self.running {
+            Some((running_white, _)) if running_white == white => (),
+            _ => {
+                self.stop(cycles);
+                self.running = Some((white, cycles));
+            },
+        }
+    }
+
+    // Stops the running clock and subtracts the time used from its team
+    pub fn stop(&mut self, cycles: u64) {
+        if let Some((white, start_cycle)) = self.running {
+            let index = clock_index(white);
+            self.remaining_cycles[index] = self.remaining_cycles[index].saturating_sub(cycles - start_cycle);
+            self.running = None;
+        }
+    }
+
+    // Adds the increment to a team's clock once their move has been made
+    pub fn increment(&mut self, white: bool) {
+        self.remaining_cycles[clock_index(white)] += self.increment_cycles;
+    }
+
+    // Returns the time a team has left, including the time used by the running clock
+    pub fn remaining(&self, white: bool, cycles: u64) -> u64 {
+        let remaining_cycles = self.remaining_cycles[clock_index(white)];
+        match self.running {
+            Some((running_white, start_cycle)) if running_white == white => remaining_cycles.saturating_sub(cycles - start_cycle),
+            _ => remaining_cycles,
+        }
+    }
+
+    // Returns true if a team has run out of time
+    pub fn flagged(&self, white: bool, cycles: u64) -> bool {
+        self.remaining(white, cycles) == 0
+    }
+}
+
+fn clock_index(white: bool) -> usize {
+    if white {
+        0
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocates_part_of_the_clock() {
+        let limits = TimeLimits::from_clock(300000, 0, 0, 0);
+        assert_eq!(limits.soft_cycles, 6000);
+        assert_eq!(limits.hard_cycles, 24000);
+
+        // More time is used per move later in the game, and most of the increment is used
+        let late_limits = TimeLimits::from_clock(300000, 4000, 0, 120);
+        assert_eq!(late_limits.soft_cycles, 18000);
+        assert_eq!(late_limits.hard_cycles, 72000);
+
+        // Low on time the hard limit keeps most of the clock
+        let low_limits = TimeLimits::from_clock(3000, 10000, 1000, 0);
+        assert_eq!(low_limits.hard_cycles, 666);
+        assert!(low_limits.soft_cycles <= low_limits.hard_cycles);
+    }
+
+    #[test]
+    fn adjusts_iteration_time() {
+        let limits = TimeLimits { soft_cycles: 1000, hard_cycles: 4000 };
+        let mut search_info = SearchInfo::new();
+        assert_eq!(limits.iteration_cycles(&search_info), 1000);
+
+        search_info.stable_iterations = STABLE_ITERATIONS;
+        assert_eq!(limits.iteration_cycles(&search_info), 500);
+
+        assert_eq!(TimeLimits::fixed(1000).iteration_cycles(&search_info), 1000);
+
+        search_info.score_drop = SCORE_DROP;
+        assert_eq!(limits.iteration_cycles(&search_info), 2000);
+    }
+
+    #[test]
+    fn clock_counts_running_team() {
+        let mut clock = ChessClock::new(1000, 100);
+
+        clock.start(true, 50);
+        assert_eq!(clock.remaining(true, 350), 700);
+        assert_eq!(clock.remaining(false, 350), 1000);
+
+        // Restarting the running clock doesn't lose the time already used
+        clock.start(true, 300);
+        assert_eq!(clock.remaining(true, 350), 700);
+
+        clock.stop(350);
+        clock.increment(true);
+        assert_eq!(clock.remaining(true, 5000), 800);
+        assert!(!clock.flagged(true, 5000));
+
+        clock.start(false, 400);
+        assert!(!clock.flagged(false, 1399));
+        assert!(clock.flagged(false, 1400));
+        clock.stop(2000);
+        assert_eq!(clock.remaining_cycles[1], 0);
+
+        // Starting the other team's clock stops the running clock
+        clock.start(true, 2000);
+        clock.start(false, 2100);
+        assert_eq!(clock.remaining_cycles[0], 700);
+        assert_eq!(clock.running, Some((false, 2100)));
+    }
+}
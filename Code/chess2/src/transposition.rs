@@ -0,0 +1,101 @@
+use crate::algorithm::Move;
+
+// Number of entries in the table used on the chess board, each entry is 12 bytes
+// Host builds can create larger tables
+pub const EMBEDDED_ENTRIES: usize = 256;
+
+// How the stored score relates to the real score of the position
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Bound {
+    Exact, // The score is the real score
+    Lower, // The real score is greater than or equal to the score (the search had a beta cutoff)
+    Upper, // The real score is less than or equal to the score (no move raised alpha)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Entry {
+    pub check: u32, // Upper 32 bits of the zobrist key, used to tell apart positions that share an index
+    pub depth: u8, // Depth that the position was searched to
+    pub bound: Bound,
+    pub score: i16, // Score from the perspective of the team to move, mate scores count half moves from this position
+    pub best_move: Option<(u8, u8, Option<u8>)>, // Initial bit, final bit, and promotion board index of the best move
+}
+
+impl Entry {
+    // Returns true if the entry best move is the same as piece_move
+    pub fn is_best_move(&self, piece_move: &Move) -> bool {
+        match self.best_move {
+            Some((initial_bit, final_bit, promotion)) => {
+                initial_bit as usize == piece_move.initial_piece_coordinates.bit
+                    && final_bit as usize == piece_move.final_piece_bit
+                    && promotion.map(|index| index as usize) == piece_move.promotion
+            },
+            None => false,
+        }
+    }
+}
+
+// Fixed size table of searched positions, indexed by zobrist key
+pub struct TranspositionTable<const N: usize> {
+    entries: [Option<Entry>; N],
+}
+
+impl<const N: usize> TranspositionTable<N> {
+    pub const fn new() -> Self {
+        TranspositionTable {
+            entries: [None; N],
+        }
+    }
+
+    // Removes every entry from the table
+    pub fn clear(&mut self) {
+        for i in 0..N {
+            self.entries[i] = None;
+        }
+    }
+
+    // Returns the entry for the position with the given key if it is in the table
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        match self.entries[(key % N as u64) as usize] {
+            Some(entry) => {
+                if entry.check == (key >> 32) as u32 {
+                    return Some(entry);
+                }
+                None
+            },
+            None => None,
+        }
+    }
+
+    // Stores a searched position
+    // Entries for the same position are only replaced by searches that are at least as deep
+    pub fn store(&mut self, key: u64, depth: usize, bound: Bound, score: i16, best_move: Option<Move>) {
+        let index = (key % N as u64) as usize;
+        let check = (key >> 32) as u32;
+
+        if let Some(entry) = self.entries[index] {
+            if entry.check == check && entry.depth as usize > depth {
+                return;
+            }
+        }
+
+        let mut compact_move = None;
+        if let Some(piece_move) = best_move {
+            compact_move = Some((piece_move.initial_piece_coordinates.bit as u8, piece_move.final_piece_bit as u8, piece_move.promotion.map(|index| index as u8)));
+        }
+
+        self.entries[index] = Some(Entry {
+            check,
+            depth: depth as u8,
+            bound,
+            score,
+            best_move: compact_move,
+        });
+    }
+}
+
+impl<const N: usize> Default for TranspositionTable<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}